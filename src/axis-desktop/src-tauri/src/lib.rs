@@ -1,6 +1,15 @@
-use std::{thread::sleep, time::Duration};
+use std::{path::PathBuf, thread::sleep, time::Duration};
 
-use tauri::AppHandle;
+use axis_protocol::clock::ClockOffset;
+use axis_protocol::handshake::Compatibility;
+use axis_protocol::logs::LogRecord;
+use axis_protocol::messages::Messages;
+use axis_protocol::transport::{Transport, DEFAULT_TCP_PORT};
+use tauri::{AppHandle, Emitter, Manager, State};
+use usb::auth::AuthenticationError;
+use usb::client::{Connection, ConnectionError};
+use usb::device::{Device, Status};
+use usb::handshake::HandshakeError;
 
 mod usb;
 
@@ -15,13 +24,76 @@ async fn test() -> String {
     "test!".to_string()
 }
 
+#[tauri::command]
+fn device_status(device: State<Device>) -> Status {
+    device.status()
+}
+
+/// The device log records received so far, one formatted line each.
+#[tauri::command]
+fn device_logs(device: State<Device>) -> Vec<String> {
+    device.log().lines()
+}
+
+/// The device log records received so far, for the frontend to filter and format itself.
+#[tauri::command]
+fn device_log_records(device: State<Device>) -> Vec<LogRecord> {
+    device.log().records().cloned().collect()
+}
+
+/// Records the device reported as dropped, by rate limiting or a full queue.
+#[tauri::command]
+fn device_log_dropped(device: State<Device>) -> u64 {
+    device.log().dropped()
+}
+
+#[tauri::command]
+fn clear_device_logs(device: State<Device>) {
+    device.log().clear();
+}
+
+#[tauri::command]
+fn save_device_logs(device: State<Device>, path: PathBuf) -> Result<(), String> {
+    device.log().save(&path).map_err(|e| e.to_string())
+}
+
+/// Set to the device's network address, e.g. `192.168.1.20`, to connect over TCP instead of
+/// looking for it on USB.
+const DEVICE_ADDR_VAR: &str = "AXIS_DEVICE_ADDR";
+
+/// Set to a device's USB serial number to connect to that one when several are plugged in.
+const DEVICE_SERIAL_VAR: &str = "AXIS_DEVICE_SERIAL";
+
+/// The key for authenticated sessions as 64 hex digits, required to connect over TCP. Without
+/// it, a random key is handed to the device over USB instead.
+const DEVICE_KEY_VAR: &str = "AXIS_DEVICE_KEY";
+
+/// How we gain access to the device after the hello.
+#[derive(Clone, Copy)]
+enum Access {
+    /// Over USB we are trusted, and hand the device the key for network clients.
+    Provision,
+    /// Over the network we have to prove that we hold the key.
+    Authenticate,
+}
+
 fn setup_app<'a>(app: &'a mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     // This one
-    let handle = app.handle();
+    let handle = app.handle().clone();
+    let key = match std::env::var(DEVICE_KEY_VAR) {
+        Ok(key) => Some(usb::auth::parse_key(&key).ok_or(format!("{DEVICE_KEY_VAR} must be 64 hex digits"))?),
+        Err(_) => None,
+    };
+    app.manage(Device::new(key));
 
-    tauri::async_runtime::spawn(async {
+    let address = std::env::var(DEVICE_ADDR_VAR).ok();
+    let serial_number = std::env::var(DEVICE_SERIAL_VAR).ok();
+    tauri::async_runtime::spawn(async move {
         loop {
-            usb::client::run().await;
+            match &address {
+                Some(address) => connect_tcp(&handle, address).await,
+                None => connect_usb(&handle, serial_number.as_deref()).await,
+            }
             tokio::time::sleep(Duration::from_secs(1)).await;
         }
     });
@@ -29,12 +101,148 @@ fn setup_app<'a>(app: &'a mut tauri::App) -> Result<(), Box<dyn std::error::Erro
     Ok(())
 }
 
+/// Talks to the device on its USB serial port until it is unplugged or stops answering.
+async fn connect_usb(handle: &AppHandle, serial_number: Option<&str>) {
+    let Some(port) = usb::client::find_port(serial_number) else {
+        set_status(handle, Status::Disconnected);
+        return;
+    };
+
+    set_status(handle, Status::Connecting { port: port.clone() });
+    let result = match usb::transport::open_serial(&port) {
+        Ok(transport) => serve(handle, Connection::new(transport), Access::Provision).await,
+        Err(e) => Err(ConnectionError::Open(e.into())),
+    };
+
+    if let Err(ConnectionError::Handshake(HandshakeError::Incompatible(_))) = result {
+        // Left alone until it is unplugged, rather than failing the handshake every second
+        while usb::client::find_port(serial_number).as_deref() == Some(port.as_str()) {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+/// Talks to the device at `address` until the connection breaks.
+async fn connect_tcp(handle: &AppHandle, address: &str) {
+    set_status(handle, Status::Connecting { port: address.to_string() });
+    let result = match usb::tcp::connect((address, DEFAULT_TCP_PORT)).await {
+        Ok(transport) => serve(handle, Connection::new(transport), Access::Authenticate).await,
+        Err(e) => Err(ConnectionError::Open(e)),
+    };
+
+    if let Err(ConnectionError::Handshake(HandshakeError::Incompatible(_))) = result {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+    }
+}
+
+/// Talks to the device over `connection` until it breaks, keeping the status up to date.
+async fn serve<T: Transport>(
+    handle: &AppHandle,
+    mut connection: Connection<T>,
+    access: Access,
+) -> Result<(), ConnectionError> {
+    let result = session(handle, &mut connection, access).await;
+    match &result {
+        Err(ConnectionError::Handshake(HandshakeError::Incompatible(info))) => {
+            set_status(handle, Status::Incompatible { info: *info })
+        }
+        Err(error) => set_status(handle, Status::Failed { reason: error.to_string() }),
+        Ok(()) => {}
+    }
+    result
+}
+
+async fn session<T: Transport>(
+    handle: &AppHandle,
+    connection: &mut Connection<T>,
+    access: Access,
+) -> Result<(), ConnectionError> {
+    let (info, compatibility) = connection.handshake().await?;
+
+    let device = handle.state::<Device>();
+    match access {
+        Access::Provision => {
+            let key = match device.key() {
+                Some(key) => key,
+                None => {
+                    let key = usb::auth::generate_key()
+                        .map_err(|e| ConnectionError::Authentication(AuthenticationError::Random(e)))?;
+                    device.set_key(key);
+                    key
+                }
+            };
+            connection.provision(Some(key)).await?;
+        }
+        Access::Authenticate => {
+            let key = device.key().ok_or(ConnectionError::Authentication(AuthenticationError::NoKey))?;
+            connection.authenticate(key).await?;
+        }
+    }
+
+    let clock = connection.sync_clock().await?;
+    device.telemetry().reset();
+    for command in usb::telemetry::subscribe() {
+        connection.command(command).await?;
+    }
+
+    let degraded = compatibility == Compatibility::Degraded;
+    set_status(handle, Status::Connected { info, degraded });
+
+    loop {
+        let message = connection.next_message().await?;
+        forward(handle, &clock, message);
+    }
+}
+
+/// Stores `status` and tells the frontend if it changed.
+fn set_status(handle: &AppHandle, status: Status) {
+    let device = handle.state::<Device>();
+    if device.status() != status {
+        device.set_status(status.clone());
+        let _ = handle.emit("device-status", status);
+    }
+}
+
+/// Passes a message pushed by the device on to the frontend, with the timestamps of events and
+/// telemetry mapped onto our clock. Log records keep the device's uptime, like its own log.
+fn forward(handle: &AppHandle, clock: &ClockOffset, message: Messages) {
+    match message {
+        Messages::Log(record) => {
+            let _ = handle.emit("device-log", usb::logs::format(&record));
+            handle.state::<Device>().log().push(record);
+        }
+        Messages::Event(mut event) => {
+            let _ = handle.emit("device-log", usb::events::format(&event));
+            event.timestamp_us = clock.to_client_us(event.timestamp_us);
+            if let Some(notification) = usb::events::notification(&event) {
+                let _ = handle.emit("device-notification", notification);
+            }
+            let _ = handle.emit("device-event", event);
+        }
+        message => {
+            let frame = handle.state::<Device>().telemetry().read(message);
+            if let Some(mut frame) = frame {
+                frame.base_timestamp_us = clock.to_client_us(frame.base_timestamp_us);
+                let _ = handle.emit("telemetry", frame);
+            }
+        }
+    }
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .setup(setup_app)
         .plugin(tauri_plugin_shell::init())
-        .invoke_handler(tauri::generate_handler![greet])
+        .invoke_handler(tauri::generate_handler![
+            greet,
+            device_status,
+            device_logs,
+            device_log_records,
+            device_log_dropped,
+            clear_device_logs,
+            save_device_logs
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,82 @@
+use axis_protocol::auth::{AuthError, ClientHandshake, Key, Nonce, Session};
+use axis_protocol::messages::{CommandError, Commands, Responses};
+
+#[derive(Debug)]
+pub enum AuthenticationError {
+    /// The device refused the challenge or our proof, see [AuthError].
+    Rejected(CommandError),
+    /// The device's proof does not match, it holds a different key.
+    Auth(AuthError),
+    /// The device answered with something other than the next handshake step.
+    UnexpectedResponse(Box<Responses>),
+    /// No random nonce could be generated.
+    Random(getrandom::Error),
+    /// There is no key to authenticate with, it is only known after provisioning the device over
+    /// USB or from the environment.
+    NoKey,
+}
+
+/// A key written as 64 hex digits.
+pub fn parse_key(hex: &str) -> Option<Key> {
+    let hex = hex.trim();
+    let mut key = Key::default();
+    if !hex.is_ascii() || hex.len() != key.len() * 2 {
+        return None;
+    }
+    for (byte, digits) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(key)
+}
+
+/// A new random pre-shared key, to be sent with [provision] and stored with the device's
+/// settings.
+pub fn generate_key() -> Result<Key, getrandom::Error> {
+    let mut key = Key::default();
+    getrandom::getrandom(&mut key)?;
+    Ok(key)
+}
+
+/// Sets the device's pre-shared key, or removes it. The device only accepts this over USB.
+pub fn provision(key: Option<Key>) -> Commands {
+    Commands::ProvisionKey { key }
+}
+
+/// Starts a session handshake, returning the command to send first.
+pub fn start(key: Key) -> Result<(ClientHandshake, Commands), AuthenticationError> {
+    let mut client_nonce = Nonce::default();
+    getrandom::getrandom(&mut client_nonce).map_err(AuthenticationError::Random)?;
+
+    let handshake = ClientHandshake::new(key, client_nonce);
+    let command = Commands::Challenge { client_nonce };
+    Ok((handshake, command))
+}
+
+/// Checks the device's answer to the challenge, returning the command to send next.
+pub fn respond(
+    handshake: &mut ClientHandshake,
+    response: Responses,
+) -> Result<Commands, AuthenticationError> {
+    match response {
+        Responses::Challenge { host_nonce } => Ok(Commands::Authenticate {
+            proof: handshake.respond(host_nonce),
+        }),
+        Responses::Error(error) => Err(AuthenticationError::Rejected(error)),
+        other => Err(AuthenticationError::UnexpectedResponse(Box::new(other))),
+    }
+}
+
+/// Checks the device's answer to our proof. The returned session has to seal every packet sent
+/// and open every packet received from now on.
+pub fn finish(
+    handshake: ClientHandshake,
+    response: Responses,
+) -> Result<Session, AuthenticationError> {
+    match response {
+        Responses::Authenticated { proof } => {
+            handshake.finish(&proof).map_err(AuthenticationError::Auth)
+        }
+        Responses::Error(error) => Err(AuthenticationError::Rejected(error)),
+        other => Err(AuthenticationError::UnexpectedResponse(Box::new(other))),
+    }
+}
@@ -1,14 +1,476 @@
-use std::borrow::Borrow;
-use futures_core::Stream;
-use futures_util::StreamExt;  // Brings in useful Stream extension traits
-use tokio;  // We'll use tokio for the runtime
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
 
+use axis_protocol::auth::Key;
+use axis_protocol::clock::ClockOffset;
+use axis_protocol::codec::{Body, CodecError, Packet, PacketCodec};
+use axis_protocol::handshake::{Compatibility, DeviceInfo};
+use axis_protocol::messages::{CommandError, Commands, Messages, Responses};
+use axis_protocol::transport::{Transport, TransportError};
+use tokio_serial::SerialPortType;
 
+use super::auth::{self, AuthenticationError};
+use super::clock::{self, ClockSyncError};
+use super::handshake::{self, HandshakeError};
+use super::link::Link;
+use super::reliable::{ReliableSender, SendError};
+use super::requests::{PendingRequest, RequestError, Requests};
 
-pub async fn run() {
-    let mut watch: nusb::hotplug::HotplugWatch = nusb::watch_devices().unwrap();
+/// USB IDs the device enumerates with.
+pub const VENDOR_ID: u16 = 0xc0de;
+pub const PRODUCT_ID: u16 = 0xcafe;
 
-    let item = watch.next().await.unwrap();
+/// Interfaces of the protocol's CDC-ACM function, which the device builds first. Ports report the
+/// communication interface on Linux and Windows and the data interface on macOS. The second
+/// function, carrying plain text logs, takes the interfaces after them.
+const PROTOCOL_INTERFACES: [u8; 2] = [0, 1];
 
-    println!("Event test: {:?}", item);
-}
\ No newline at end of file
+/// How long [Connection::request] waits for the device to answer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum ConnectionError {
+    /// The serial port or the TCP connection could not be opened.
+    Open(io::Error),
+    /// The link failed, e.g. the serial port went away.
+    Link(String),
+    Codec(CodecError),
+    Send(SendError),
+    Request(RequestError),
+    Handshake(HandshakeError),
+    Authentication(AuthenticationError),
+    Clock(ClockSyncError),
+    /// The device refused a command.
+    Refused(CommandError),
+    /// The device answered a command with something other than [Responses::Ok].
+    UnexpectedResponse(Box<Responses>),
+    /// The device stopped sending anything, heartbeats included.
+    LinkLost,
+    /// The port closed, usually because the device was unplugged.
+    Closed,
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionError::Open(e) => write!(f, "failed to connect: {e}"),
+            ConnectionError::Link(e) => write!(f, "the link failed: {e}"),
+            ConnectionError::Codec(e) => e.fmt(f),
+            ConnectionError::Send(e) => write!(f, "failed to send a command: {e}"),
+            ConnectionError::Request(e) => write!(f, "the device did not answer: {e:?}"),
+            ConnectionError::Handshake(e) => write!(f, "handshake failed: {e:?}"),
+            ConnectionError::Authentication(e) => write!(f, "authentication failed: {e:?}"),
+            ConnectionError::Clock(e) => write!(f, "failed to synchronize the clock: {e:?}"),
+            ConnectionError::Refused(e) => write!(f, "the device refused a command: {e:?}"),
+            ConnectionError::UnexpectedResponse(r) => write!(f, "unexpected response: {r:?}"),
+            ConnectionError::LinkLost => write!(f, "the device stopped responding"),
+            ConnectionError::Closed => write!(f, "the device disconnected"),
+        }
+    }
+}
+
+impl From<CodecError> for ConnectionError {
+    fn from(e: CodecError) -> Self {
+        ConnectionError::Codec(e)
+    }
+}
+
+/// The protocol port of a plugged in device, or of the one with `serial_number` if given. The
+/// device enumerates two serial ports, the other one carries plain text logs.
+pub fn find_port(serial_number: Option<&str>) -> Option<String> {
+    tokio_serial::available_ports()
+        .ok()?
+        .into_iter()
+        .find(|port| {
+            let SerialPortType::UsbPort(usb) = &port.port_type else {
+                return false;
+            };
+            usb.vid == VENDOR_ID
+                && usb.pid == PRODUCT_ID
+                && usb.interface.is_some_and(|interface| PROTOCOL_INTERFACES.contains(&interface))
+                && serial_number.is_none_or(|serial_number| usb.serial_number.as_deref() == Some(serial_number))
+        })
+        .map(|port| port.port_name)
+}
+
+/// A device connected over any [Transport], such as its USB serial port or TCP. Received
+/// packets are reassembled and opened by the protocol's [PacketCodec], which also seals and
+/// splits the packets we send.
+pub struct Connection<T> {
+    transport: T,
+    codec: PacketCodec,
+    link: Link,
+    requests: Requests,
+    reliable: ReliableSender,
+    /// Messages that arrived while waiting for a response.
+    messages: VecDeque<Messages>,
+}
+
+impl<T: Transport> Connection<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            codec: PacketCodec::new(),
+            link: Link::new(),
+            requests: Requests::new(),
+            reliable: ReliableSender::new(),
+            messages: VecDeque::new(),
+        }
+    }
+
+    /// Says hello to the device, which has to come before anything else is sent. Fails with
+    /// [HandshakeError::Incompatible] if the device speaks a protocol we cannot decode.
+    pub async fn handshake(&mut self) -> Result<(DeviceInfo, Compatibility), ConnectionError> {
+        let response = self.request(handshake::hello()).await?;
+        handshake::accept(response).map_err(ConnectionError::Handshake)
+    }
+
+    /// Hands the device the key clients on the network authenticate with, or removes it. The
+    /// device only accepts this over USB.
+    pub async fn provision(&mut self, key: Option<Key>) -> Result<(), ConnectionError> {
+        match self.request(auth::provision(key)).await? {
+            Responses::Ok => Ok(()),
+            Responses::Error(error) => Err(ConnectionError::Authentication(AuthenticationError::Rejected(error))),
+            other => Err(ConnectionError::Authentication(AuthenticationError::UnexpectedResponse(Box::new(other)))),
+        }
+    }
+
+    /// Proves that we hold `key` and seals every packet from then on, which the device requires
+    /// before anything but the hello over the network.
+    pub async fn authenticate(&mut self, key: Key) -> Result<(), ConnectionError> {
+        let (mut handshake, command) = auth::start(key).map_err(ConnectionError::Authentication)?;
+        let response = self.request(command).await?;
+        let command = auth::respond(&mut handshake, response).map_err(ConnectionError::Authentication)?;
+        let response = self.request(command).await?;
+        let session = auth::finish(handshake, response).map_err(ConnectionError::Authentication)?;
+
+        // The device seals everything after its answer, which was the last packet decoded
+        self.codec.set_session(Some(session));
+        Ok(())
+    }
+
+    /// Sets the device's clock if it is off ours, and measures the offset mapping the uptime
+    /// its timestamps count onto our clock.
+    pub async fn sync_clock(&mut self) -> Result<ClockOffset, ConnectionError> {
+        let reading = clock::accept_reading(self.request(Commands::ReadClock).await?).map_err(ConnectionError::Clock)?;
+        if clock::needs_setting(&reading) {
+            match self.command(clock::set()).await {
+                // Without a working RTC the uptime can still be mapped
+                Err(ConnectionError::Refused(_)) => {}
+                result => result?,
+            }
+        }
+
+        let mut best = clock::accept_sync(self.request(clock::sync()).await?).map_err(ConnectionError::Clock)?;
+        for _ in 1..clock::SYNC_ROUNDS {
+            let offset = clock::accept_sync(self.request(clock::sync()).await?).map_err(ConnectionError::Clock)?;
+            best = best.best(offset);
+        }
+        Ok(best)
+    }
+
+    /// Sends `command`, which the device answers with [Responses::Ok] when it succeeds.
+    pub async fn command(&mut self, command: Commands) -> Result<(), ConnectionError> {
+        match self.request(command).await? {
+            Responses::Ok => Ok(()),
+            Responses::Error(error) => Err(ConnectionError::Refused(error)),
+            other => Err(ConnectionError::UnexpectedResponse(Box::new(other))),
+        }
+    }
+
+    /// Sends `command` and waits for the device's answer.
+    pub async fn request(&mut self, command: Commands) -> Result<Responses, ConnectionError> {
+        let mut request = self.requests.begin(command).map_err(ConnectionError::Request)?;
+        self.send(&request).await?;
+
+        let deadline = Instant::now() + REQUEST_TIMEOUT;
+        loop {
+            if let Some(response) = request.try_response() {
+                return response.map_err(ConnectionError::Request);
+            }
+            if Instant::now() >= deadline {
+                self.requests.cancel(request.header.get_sequence());
+                return Err(ConnectionError::Request(RequestError::Timeout));
+            }
+            if let Some(message) = self.step().await? {
+                self.messages.push_back(message);
+            }
+        }
+    }
+
+    /// Writes the command of `request`, resending it until the device acknowledges it.
+    pub async fn send(&mut self, request: &PendingRequest) -> Result<(), ConnectionError> {
+        let packet = self
+            .reliable
+            .send(request, &mut self.codec)
+            .map_err(ConnectionError::Send)?;
+        self.write_sealed(&packet).await
+    }
+
+    /// Waits for the next message pushed by the device, sending heartbeats meanwhile.
+    pub async fn next_message(&mut self) -> Result<Messages, ConnectionError> {
+        if let Some(message) = self.messages.pop_front() {
+            return Ok(message);
+        }
+        loop {
+            if let Some(message) = self.step().await? {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Handles one received packet or timer tick. Receiving is cancel safe and writes happen
+    /// after `select!` picked a branch, so no frame is ever cut short.
+    async fn step(&mut self) -> Result<Option<Messages>, ConnectionError> {
+        let wakeup = match self.reliable.next_due() {
+            Some(due) => due.min(self.link.next_heartbeat()),
+            None => self.link.next_heartbeat(),
+        };
+
+        tokio::select! {
+            packet = self.transport.receive() => {
+                let packet = match packet {
+                    Ok(packet) => self.codec.decode_packet(packet),
+                    Err(TransportError::Frame(e)) => {
+                        self.codec.record_frame_error(e);
+                        None
+                    }
+                    Err(TransportError::Closed) => return Err(ConnectionError::Closed),
+                    Err(TransportError::Link(e)) => return Err(ConnectionError::Link(format!("{e:?}"))),
+                };
+                let Some(packet) = packet else {
+                    return Ok(None);
+                };
+
+                self.link.seen();
+                match packet.body {
+                    Body::Message(message) => return Ok(Some(message)),
+                    Body::Ack(acked) => {
+                        self.reliable.ack(acked);
+                    }
+                    Body::Response(response) => {
+                        self.requests.resolve(response);
+                    }
+                    Body::Command(_) | Body::Heartbeat(_) => {}
+                }
+                Ok(None)
+            }
+            _ = tokio::time::sleep_until(wakeup.into()) => {
+                if !self.link.is_alive() {
+                    return Err(ConnectionError::LinkLost);
+                }
+                if let Some(heartbeat) = self.link.poll_heartbeat() {
+                    let sequence = self.requests.next_sequence();
+                    let packet = self.codec.seal(Packet::new(sequence, Body::Heartbeat(heartbeat)))?;
+                    self.write_sealed(&packet).await?;
+                }
+                // Requests that ran out of retransmits were cancelled, their callers see why
+                for packet in self.reliable.poll(&self.requests).resend {
+                    self.write_sealed(&packet).await?;
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Sends a packet returned by [PacketCodec::seal], as fragments if it is too large for a
+    /// single frame.
+    async fn write_sealed(&mut self, packet: &[u8]) -> Result<(), ConnectionError> {
+        for packet in self.codec.split(packet)? {
+            self.transport.send(&packet).await.map_err(|e| match e {
+                TransportError::Closed => ConnectionError::Closed,
+                e => ConnectionError::Link(format!("{e:?}")),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axis_protocol::auth::{Authenticator, Session};
+    use axis_protocol::clock::ClockReading;
+    use axis_protocol::decode::{decode_packet, open, Decoded};
+    use axis_protocol::events::{Event, EventKind};
+    use axis_protocol::fragment::MAX_FRAGMENT_LEN;
+    use axis_protocol::messages::{CommandError, Response};
+    use axis_protocol::transport::{loopback, FramedStream, LoopbackStream};
+    use axis_protocol::{encode_packet, reliability, MessageHeader, MessageId};
+
+    use super::*;
+    use crate::usb::transport::MAX_FRAME_LEN;
+
+    type Loopback = FramedStream<LoopbackStream, MAX_FRAME_LEN>;
+
+    /// Plays the device: acks every command and answers it with `answer`, after pushing an
+    /// event. Ignores the first `lost` commands, as if they never arrived.
+    async fn device(mut link: Loopback, mut lost: usize, answer: fn(Commands) -> Responses) {
+        let mut sequence = 0u8;
+        let mut next_header = |message_id| {
+            sequence = sequence.wrapping_add(1);
+            MessageHeader::new(sequence, message_id)
+        };
+
+        let mut buf = [0u8; MAX_FRAGMENT_LEN];
+        while let Ok(packet) = link.receive().await {
+            let Ok(Decoded::Packet(packet)) = decode_packet(packet) else {
+                panic!("the client sent an undecodable packet");
+            };
+            let Body::Command(command) = packet.body else {
+                continue;
+            };
+            if lost > 0 {
+                lost -= 1;
+                continue;
+            }
+
+            let ack = reliability::encode_ack(next_header(MessageId::Ack).get_sequence(), packet.sequence, &mut buf);
+            link.send(ack.unwrap()).await.unwrap();
+
+            let event = Messages::Event(Event::new(0, EventKind::ShotStarted));
+            let event = encode_packet(next_header(MessageId::Message), &event, &mut buf);
+            link.send(event.unwrap()).await.unwrap();
+
+            let response = Response::new(packet.sequence, answer(command));
+            let response = encode_packet(next_header(MessageId::Response), &response, &mut buf);
+            link.send(response.unwrap()).await.unwrap();
+        }
+    }
+
+    /// Plays a device on the network holding `key`: answers the challenge and the proof, then
+    /// only accepts sealed commands, which it answers with sealed `Pong`s.
+    async fn authenticating_device(mut link: Loopback, key: Key) {
+        let mut authenticator = Authenticator::new();
+        authenticator.provision(Some(key));
+        let mut session: Option<Session> = None;
+        let mut sequence = 0u8;
+
+        while let Ok(packet) = link.receive().await {
+            let opener = session.as_mut().map(|session| &mut session.opener);
+            let packet = open(opener, decode_packet(packet).unwrap()).expect("the client did not seal its packet");
+            let Body::Command(command) = packet.body else {
+                continue;
+            };
+
+            let (answer, opened) = match command {
+                Commands::Challenge { client_nonce } => {
+                    let host_nonce = authenticator.challenge(client_nonce, [9; 16]).unwrap();
+                    (Responses::Challenge { host_nonce }, None)
+                }
+                Commands::Authenticate { proof } => match authenticator.authenticate(&proof) {
+                    Ok((proof, opened)) => (Responses::Authenticated { proof }, Some(opened)),
+                    Err(e) => (Responses::Error(CommandError::Auth(e)), None),
+                },
+                _ => (Responses::Pong, None),
+            };
+
+            let mut buf = [0u8; MAX_FRAGMENT_LEN];
+            sequence = sequence.wrapping_add(1);
+            let ack = reliability::encode_ack(sequence, packet.sequence, &mut buf).unwrap();
+            link.send(&seal(&mut session, ack)).await.unwrap();
+
+            sequence = sequence.wrapping_add(1);
+            let response = Response::new(packet.sequence, answer);
+            let header = MessageHeader::new(sequence, MessageId::Response);
+            let response = encode_packet(header, &response, &mut buf).unwrap();
+            link.send(&seal(&mut session, response)).await.unwrap();
+
+            // Sealed from after the answer to the proof on
+            session = opened.or(session);
+        }
+    }
+
+    fn seal(session: &mut Option<Session>, packet: &[u8]) -> Vec<u8> {
+        let mut sealed = [0u8; MAX_FRAGMENT_LEN];
+        match session {
+            Some(session) => session.sealer.seal(packet, &mut sealed).unwrap().to_vec(),
+            None => packet.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn request_gets_its_response_and_keeps_pushed_messages() {
+        let (host, client) = loopback::<MAX_FRAME_LEN>();
+        let client = async move {
+            let mut connection = Connection::new(client);
+            let response = connection.request(Commands::Ping).await.unwrap();
+            assert!(matches!(response, Responses::Pong));
+
+            // The event pushed while waiting is still delivered
+            let message = connection.next_message().await.unwrap();
+            assert!(matches!(message, Messages::Event(event) if event.kind == EventKind::ShotStarted));
+        };
+        tokio::join!(device(host, 0, |_| Responses::Pong), client);
+    }
+
+    #[tokio::test]
+    async fn lost_command_is_resent() {
+        let (host, client) = loopback::<MAX_FRAME_LEN>();
+        let client = async move {
+            let mut connection = Connection::new(client);
+            let response = connection.request(Commands::Ping).await.unwrap();
+            assert!(matches!(response, Responses::Pong));
+        };
+        tokio::join!(device(host, 1, |_| Responses::Pong), client);
+    }
+
+    #[tokio::test]
+    async fn closed_link_ends_the_connection() {
+        let (host, client) = loopback::<MAX_FRAME_LEN>();
+        drop(host);
+        let mut connection = Connection::new(client);
+        assert!(matches!(connection.next_message().await, Err(ConnectionError::Closed)));
+    }
+
+    #[tokio::test]
+    async fn authenticated_session_is_sealed() {
+        let (host, client) = loopback::<MAX_FRAME_LEN>();
+        let client = async move {
+            let mut connection = Connection::new(client);
+            connection.authenticate([7; 32]).await.unwrap();
+            let response = connection.request(Commands::Ping).await.unwrap();
+            assert!(matches!(response, Responses::Pong));
+        };
+        tokio::join!(authenticating_device(host, [7; 32]), client);
+    }
+
+    #[tokio::test]
+    async fn wrong_key_is_rejected() {
+        let (host, client) = loopback::<MAX_FRAME_LEN>();
+        let client = async move {
+            let mut connection = Connection::new(client);
+            let result = connection.authenticate([1; 32]).await;
+            assert!(matches!(
+                result,
+                Err(ConnectionError::Authentication(AuthenticationError::Rejected(_)))
+            ));
+        };
+        tokio::join!(authenticating_device(host, [7; 32]), client);
+    }
+
+    #[tokio::test]
+    async fn clock_is_set_and_synchronized() {
+        fn answer(command: Commands) -> Responses {
+            match command {
+                Commands::ReadClock => Responses::Clock(ClockReading { unix_time: None, uptime_us: 0 }),
+                Commands::SetClock { unix_time } => {
+                    assert!(unix_time.abs_diff(clock::now_us() / 1_000_000) <= 1);
+                    Responses::Ok
+                }
+                Commands::SyncClock { client_time_us } => Responses::ClockSync { client_time_us, uptime_us: 0 },
+                _ => Responses::Error(CommandError::Unsupported),
+            }
+        }
+
+        let (host, client) = loopback::<MAX_FRAME_LEN>();
+        let client = async move {
+            let mut connection = Connection::new(client);
+            let offset = connection.sync_clock().await.unwrap();
+            // The device booted about now
+            assert!(offset.to_client_us(0).abs_diff(clock::now_us()) < 1_000_000);
+        };
+        tokio::join!(device(host, 0, answer), client);
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axis_protocol::clock::{ClockOffset, ClockReading};
+use axis_protocol::messages::{CommandError, Commands, Responses};
+
+/// How far the device's clock may be off ours before it is set on connect.
+pub const MAX_DRIFT_S: u64 = 2;
+
+/// [sync] exchanges per connection, of which the one with the shortest round trip is kept.
+pub const SYNC_ROUNDS: usize = 4;
+
+#[derive(Debug)]
+pub enum ClockSyncError {
+    /// The device refused the command, see [ClockError](axis_protocol::clock::ClockError).
+    Rejected(CommandError),
+    /// The device answered with something other than its clock.
+    UnexpectedResponse(Box<Responses>),
+}
+
+/// Microseconds since the Unix epoch on this machine.
+pub fn now_us() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_micros() as u64)
+}
+
+/// Whether the device's clock is unset or more than [MAX_DRIFT_S] off ours.
+pub fn needs_setting(reading: &ClockReading) -> bool {
+    let now = now_us() / 1_000_000;
+    reading.unix_time.is_none_or(|unix_time| unix_time.abs_diff(now) > MAX_DRIFT_S)
+}
+
+/// Sets the device's clock to ours.
+pub fn set() -> Commands {
+    Commands::SetClock {
+        unix_time: now_us() / 1_000_000,
+    }
+}
+
+/// Starts measuring the offset between the device's uptime and our clock. Repeat a few times
+/// and keep the [ClockOffset::best] result.
+pub fn sync() -> Commands {
+    Commands::SyncClock {
+        client_time_us: now_us(),
+    }
+}
+
+/// Turns the device's answer to [sync] into the offset mapping its timestamps onto our clock.
+/// Call it as soon as the response arrives.
+pub fn accept_sync(response: Responses) -> Result<ClockOffset, ClockSyncError> {
+    match response {
+        Responses::ClockSync {
+            client_time_us,
+            uptime_us,
+        } => Ok(ClockOffset::measure(client_time_us, now_us(), uptime_us)),
+        Responses::Error(error) => Err(ClockSyncError::Rejected(error)),
+        other => Err(ClockSyncError::UnexpectedResponse(Box::new(other))),
+    }
+}
+
+/// Checks the device's answer to [Commands::ReadClock].
+pub fn accept_reading(response: Responses) -> Result<ClockReading, ClockSyncError> {
+    match response {
+        Responses::Clock(reading) => Ok(reading),
+        Responses::Error(error) => Err(ClockSyncError::Rejected(error)),
+        other => Err(ClockSyncError::UnexpectedResponse(Box::new(other))),
+    }
+}
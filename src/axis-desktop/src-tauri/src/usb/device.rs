@@ -0,0 +1,72 @@
+use std::sync::{Mutex, MutexGuard};
+
+use axis_protocol::auth::Key;
+use axis_protocol::handshake::DeviceInfo;
+use serde::Serialize;
+
+use super::logs::DeviceLog;
+use super::telemetry::TelemetryReader;
+
+/// What the frontend is told about the device connection.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[cfg_attr(test, derive(ts_rs::TS), ts(export))]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum Status {
+    /// No device is plugged in.
+    Disconnected,
+    /// The port is open and the handshake is under way.
+    Connecting { port: String },
+    /// `degraded` is set when the device speaks an older protocol version, so features it does
+    /// not know yet are unavailable.
+    Connected { info: DeviceInfo, degraded: bool },
+    /// The device speaks a protocol we cannot decode, and is left alone until it is unplugged.
+    Incompatible { info: DeviceInfo },
+    /// The connection broke or the handshake failed. It is retried shortly.
+    Failed { reason: String },
+}
+
+/// State of the connected device, shared between the connection task and Tauri commands.
+pub struct Device {
+    status: Mutex<Status>,
+    /// Kept across reconnects, so the logs leading up to a disconnect can still be saved.
+    log: Mutex<DeviceLog>,
+    /// The key for authenticated sessions over the network. Like on the device, it only lives
+    /// in memory.
+    key: Mutex<Option<Key>>,
+    telemetry: Mutex<TelemetryReader>,
+}
+
+impl Device {
+    pub fn new(key: Option<Key>) -> Self {
+        Self {
+            status: Mutex::new(Status::Disconnected),
+            log: Mutex::new(DeviceLog::new()),
+            key: Mutex::new(key),
+            telemetry: Mutex::new(TelemetryReader::new()),
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn set_status(&self, status: Status) {
+        *self.status.lock().unwrap() = status;
+    }
+
+    pub fn key(&self) -> Option<Key> {
+        *self.key.lock().unwrap()
+    }
+
+    pub fn set_key(&self, key: Key) {
+        *self.key.lock().unwrap() = Some(key);
+    }
+
+    pub fn telemetry(&self) -> MutexGuard<'_, TelemetryReader> {
+        self.telemetry.lock().unwrap()
+    }
+
+    pub fn log(&self) -> MutexGuard<'_, DeviceLog> {
+        self.log.lock().unwrap()
+    }
+}
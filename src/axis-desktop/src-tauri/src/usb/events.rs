@@ -0,0 +1,64 @@
+use axis_protocol::events::{Event, EventKind, Severity};
+use serde::Serialize;
+
+/// An event brought to the user's attention, as the frontend gets it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Notification {
+    pub severity: Severity,
+    pub code: u16,
+    pub message: String,
+}
+
+/// A human readable description of `kind`, for the device log and notifications.
+pub fn describe(kind: &EventKind) -> String {
+    match kind {
+        EventKind::ThermocoupleFault(fault) => {
+            let mut causes = Vec::new();
+            if fault.open_circuit {
+                causes.push("open circuit");
+            }
+            if fault.short_to_gnd {
+                causes.push("short to GND");
+            }
+            if fault.short_to_vcc {
+                causes.push("short to VCC");
+            }
+            format!("Thermocouple fault: {}", causes.join(", "))
+        }
+        EventKind::I2cFailure { device } => format!("I2C device {:?} is not responding", device),
+        EventKind::OverTemperature { temperature, limit } => {
+            format!("Over temperature: {:.1} exceeds the {:.1} limit", temperature, limit)
+        }
+        EventKind::ShotStarted => "Shot started".to_string(),
+        EventKind::ShotStopped { duration_ms } => {
+            format!("Shot stopped after {:.1} s", *duration_ms as f32 / 1000.0)
+        }
+        EventKind::ModeChanged { from, to } => format!("Mode changed from {:?} to {:?}", from, to),
+    }
+}
+
+/// Whether `event` should be brought to the user's attention rather than only logged.
+pub fn should_notify(event: &Event) -> bool {
+    event.severity >= Severity::Warning
+}
+
+/// The notification for `event`, if it [should_notify].
+pub fn notification(event: &Event) -> Option<Notification> {
+    should_notify(event).then(|| Notification {
+        severity: event.severity,
+        code: event.code,
+        message: describe(&event.kind),
+    })
+}
+
+/// Formats `event` as a single line for the device log, in the same layout as
+/// [logs::format](super::logs::format).
+pub fn format(event: &Event) -> String {
+    format!(
+        "[{:>12.3}s] {:?} E{:03}: {}",
+        event.timestamp_us as f64 / 1_000_000.0,
+        event.severity,
+        event.code,
+        describe(&event.kind)
+    )
+}
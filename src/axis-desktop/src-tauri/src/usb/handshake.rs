@@ -0,0 +1,33 @@
+use axis_protocol::handshake::{Compatibility, DeviceInfo, Hello, PROTOCOL_VERSION};
+use axis_protocol::messages::{CommandError, Commands, Responses};
+
+#[derive(Debug)]
+pub enum HandshakeError {
+    /// The device speaks a protocol major version we cannot decode.
+    Incompatible(DeviceInfo),
+    /// The device refused our [Hello].
+    Rejected(CommandError),
+    /// The device answered with something other than a hello.
+    UnexpectedResponse(Box<Responses>),
+}
+
+/// The command that opens a session. Must be the first thing sent after connecting.
+pub fn hello() -> Commands {
+    Commands::Hello(Hello::new())
+}
+
+/// Checks the device's answer to [hello], returning what it told us about itself if we can talk
+/// to it.
+pub fn accept(response: Responses) -> Result<(DeviceInfo, Compatibility), HandshakeError> {
+    let info = match response {
+        Responses::Hello(info) => info,
+        Responses::Error(error) => return Err(HandshakeError::Rejected(error)),
+        other => return Err(HandshakeError::UnexpectedResponse(Box::new(other))),
+    };
+
+    match PROTOCOL_VERSION.compatibility(&info.protocol) {
+        Compatibility::Incompatible => Err(HandshakeError::Incompatible(info)),
+        // Degraded: the device is older, messages it does not know yet are unavailable
+        compatibility => Ok((info, compatibility)),
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+use axis_protocol::heartbeat::{Heartbeat, DEFAULT_LINK_TIMEOUT_MS, HEARTBEAT_INTERVAL_MS};
+
+/// Tracks whether the device is still talking to us, and when to send our next heartbeat.
+pub struct Link {
+    epoch: Instant,
+    /// Starts out as the time the link opened, so the device gets a full timeout to answer.
+    last_seen: Instant,
+    next_heartbeat: Instant,
+}
+
+impl Link {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            epoch: now,
+            last_seen: now,
+            next_heartbeat: now,
+        }
+    }
+
+    /// Called for every valid packet from the device, heartbeats included.
+    pub fn seen(&mut self) {
+        self.last_seen = Instant::now();
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.last_seen.elapsed() < Duration::from_millis(DEFAULT_LINK_TIMEOUT_MS as u64)
+    }
+
+    /// When [poll_heartbeat](Self::poll_heartbeat) has the next heartbeat to send.
+    pub fn next_heartbeat(&self) -> Instant {
+        self.next_heartbeat
+    }
+
+    /// Returns a heartbeat to send if one is due. Its sequence number comes from
+    /// [Requests::next_sequence](super::requests::Requests::next_sequence), like every other
+    /// packet's.
+    pub fn poll_heartbeat(&mut self) -> Option<Heartbeat> {
+        let now = Instant::now();
+        if now < self.next_heartbeat {
+            return None;
+        }
+        self.next_heartbeat = now + Duration::from_millis(HEARTBEAT_INTERVAL_MS);
+
+        Some(Heartbeat {
+            uptime_ms: self.epoch.elapsed().as_millis() as u32,
+        })
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use axis_protocol::logs::{LogLevel, LogRecord};
+
+/// Most device log records kept in memory.
+const MAX_RECORDS: usize = 2000;
+
+/// Formats `record` as a single line, in the same layout as [events::format](super::events::format).
+pub fn format(record: &LogRecord) -> String {
+    let mut line = format!(
+        "[{:>12.3}s] {:<5} {}: {}",
+        record.timestamp_us as f64 / 1_000_000.0,
+        level_name(record.level),
+        record.module,
+        record.text
+    );
+    if record.truncated {
+        line.push_str("...");
+    }
+    line
+}
+
+fn level_name(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::Trace => "TRACE",
+        LogLevel::Debug => "DEBUG",
+        LogLevel::Info => "INFO",
+        LogLevel::Warn => "WARN",
+        LogLevel::Error => "ERROR",
+    }
+}
+
+/// The most recent log records forwarded by the device, so they can be shown and saved.
+pub struct DeviceLog {
+    records: VecDeque<LogRecord>,
+    /// Records the device reported as dropped, by rate limiting or a full queue.
+    dropped: u64,
+}
+
+impl DeviceLog {
+    pub fn new() -> Self {
+        Self {
+            records: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    pub fn push(&mut self, record: LogRecord) {
+        self.dropped += record.dropped as u64;
+        if self.records.len() == MAX_RECORDS {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Every kept record formatted with [format], oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        self.records.iter().map(format).collect()
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &LogRecord> {
+        self.records.iter()
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.dropped = 0;
+    }
+
+    /// Writes every kept record to `path`, one line each.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        for record in &self.records {
+            if record.dropped > 0 {
+                writeln!(file, "... {} records dropped by the device", record.dropped)?;
+            }
+            writeln!(file, "{}", format(record))?;
+        }
+        file.flush()
+    }
+}
@@ -1 +1,13 @@
-pub mod client;
\ No newline at end of file
+pub mod auth;
+pub mod client;
+pub mod clock;
+pub mod device;
+pub mod events;
+pub mod handshake;
+pub mod link;
+pub mod logs;
+pub mod reliable;
+pub mod requests;
+pub mod tcp;
+pub mod telemetry;
+pub mod transport;
@@ -0,0 +1,115 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use axis_protocol::codec::{Body, CodecError, Packet, PacketCodec};
+use axis_protocol::reliability::{
+    ReliabilityError, Retransmit, RetransmitQueue, DEFAULT_ACK_TIMEOUT_MS, DEFAULT_MAX_RETRANSMITS,
+};
+
+use super::requests::{PendingRequest, Requests};
+
+/// Largest sealed command that can be retransmitted.
+const MAX_PACKET_LEN: usize = 1024;
+/// Most commands waiting for an ack at once.
+const MAX_UNACKED: usize = 32;
+
+#[derive(Debug)]
+pub enum SendError {
+    /// The command could not be serialized or sealed.
+    Codec(CodecError),
+    Reliability(ReliabilityError),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Codec(e) => e.fmt(f),
+            SendError::Reliability(e) => write!(f, "failed to track the command: {e:?}"),
+        }
+    }
+}
+
+/// What [ReliableSender::poll] found due.
+#[derive(Debug, Default)]
+pub struct Due {
+    /// Commands to write again, byte for byte as first sealed.
+    pub resend: Vec<Vec<u8>>,
+    /// Sequence numbers of commands that ran out of retransmits. Their requests are cancelled.
+    pub gave_up: Vec<u8>,
+}
+
+/// Keeps sent commands until the device acknowledges them, resending them when the ack does not
+/// arrive in time.
+///
+/// Commands are kept as sealed, since a sealed packet must be resent rather than sealed again,
+/// see [Sealer](axis_protocol::auth::Sealer). They are split into fragments again for every
+/// send.
+pub struct ReliableSender {
+    queue: Box<RetransmitQueue<MAX_UNACKED, MAX_PACKET_LEN>>,
+    epoch: Instant,
+}
+
+impl ReliableSender {
+    pub fn new() -> Self {
+        Self {
+            queue: Box::new(RetransmitQueue::new(
+                DEFAULT_ACK_TIMEOUT_MS,
+                DEFAULT_MAX_RETRANSMITS,
+            )),
+            epoch: Instant::now(),
+        }
+    }
+
+    /// Seals `request` with `codec` and starts waiting for its ack. Returns the packet to
+    /// send.
+    pub fn send(
+        &mut self,
+        request: &PendingRequest,
+        codec: &mut PacketCodec,
+    ) -> Result<Vec<u8>, SendError> {
+        let sequence = request.header.get_sequence();
+        let packet = codec
+            .seal(Packet::new(sequence, Body::Command(request.command.clone())))
+            .map_err(SendError::Codec)?;
+
+        self.queue
+            .track(sequence, &packet, self.now_ms())
+            .map_err(SendError::Reliability)?;
+
+        Ok(packet)
+    }
+
+    /// Stops resending the command with `sequence`. Returns `false` if none was waiting, e.g.
+    /// because an earlier ack for it already arrived.
+    pub fn ack(&mut self, sequence: u8) -> bool {
+        self.queue.ack(sequence)
+    }
+
+    /// When [poll](Self::poll) has something to do next, if any command is waiting.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.queue
+            .next_due()
+            .map(|due_ms| self.epoch + Duration::from_millis(due_ms))
+    }
+
+    /// Returns the commands to resend now. Commands that ran out of retransmits are cancelled
+    /// in `requests`.
+    pub fn poll(&mut self, requests: &Requests) -> Due {
+        let now = self.now_ms();
+        let mut due = Due::default();
+        while let Some(retransmit) = self.queue.poll(now) {
+            match retransmit {
+                Retransmit::Resend(packet) => due.resend.push(packet.to_vec()),
+                Retransmit::GaveUp { sequence } => {
+                    requests.cancel(sequence);
+                    due.gave_up.push(sequence);
+                }
+            }
+        }
+        due
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use axis_protocol::messages::{Commands, Response, Responses};
+use axis_protocol::{MessageHeader, MessageId};
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+#[derive(Debug)]
+pub enum RequestError {
+    /// Every sequence number is already waiting on a response.
+    TooManyPending,
+    /// The device did not answer within the timeout.
+    Timeout,
+    /// The command ran out of retransmits, or the tracker was dropped before the device answered.
+    Cancelled,
+}
+
+/// Tracks commands sent to the device and matches each [Response] back to its request by the
+/// sequence number in its [MessageHeader].
+///
+/// It numbers every packet sent in a session, not only commands: a sealed session requires
+/// sequence numbers to count up across all packet kinds, see
+/// [Sealer](axis_protocol::auth::Sealer).
+pub struct Requests {
+    inner: Mutex<RequestsInner>,
+}
+
+struct RequestsInner {
+    next_sequence: u8,
+    pending: HashMap<u8, oneshot::Sender<Responses>>,
+}
+
+/// A command waiting to be written to the device, and the handle to await its response with.
+pub struct PendingRequest {
+    pub header: MessageHeader,
+    pub command: Commands,
+    receiver: oneshot::Receiver<Responses>,
+}
+
+impl PendingRequest {
+    /// The response, if it arrived already. The caller drives the connection until it does, see
+    /// [Connection::request](super::client::Connection::request).
+    pub fn try_response(&mut self) -> Option<Result<Responses, RequestError>> {
+        match self.receiver.try_recv() {
+            Ok(body) => Some(Ok(body)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Closed) => Some(Err(RequestError::Cancelled)),
+        }
+    }
+}
+
+impl Requests {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(RequestsInner {
+                next_sequence: 0,
+                pending: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Assigns the next free sequence number to `command`.
+    pub fn begin(&self, command: Commands) -> Result<PendingRequest, RequestError> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let start = inner.next_sequence;
+        let sequence = loop {
+            let candidate = inner.next_sequence;
+            inner.next_sequence = inner.next_sequence.wrapping_add(1);
+            if !inner.pending.contains_key(&candidate) {
+                break candidate;
+            }
+            if inner.next_sequence == start {
+                return Err(RequestError::TooManyPending);
+            }
+        };
+
+        let (sender, receiver) = oneshot::channel();
+        inner.pending.insert(sequence, sender);
+
+        Ok(PendingRequest {
+            header: MessageHeader::new(sequence, MessageId::Command),
+            command,
+            receiver,
+        })
+    }
+
+    /// Takes the next sequence number for a packet nobody waits on an answer to, such as a
+    /// heartbeat.
+    pub fn next_sequence(&self) -> u8 {
+        let mut inner = self.inner.lock().unwrap();
+        let sequence = inner.next_sequence;
+        inner.next_sequence = inner.next_sequence.wrapping_add(1);
+        sequence
+    }
+
+    /// Hands `response` to whoever is waiting on its sequence number. Returns `false` if nobody
+    /// is, e.g. because the request already timed out.
+    pub fn resolve(&self, response: Response) -> bool {
+        let sender = self.inner.lock().unwrap().pending.remove(&response.sequence);
+        match sender {
+            Some(sender) => sender.send(response.body).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Fails the request waiting on `sequence` with [RequestError::Cancelled].
+    pub fn cancel(&self, sequence: u8) -> bool {
+        self.inner.lock().unwrap().pending.remove(&sequence).is_some()
+    }
+}
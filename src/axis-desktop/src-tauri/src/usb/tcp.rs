@@ -0,0 +1,19 @@
+use std::io;
+
+use axis_protocol::transport::FramedStream;
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+use super::transport::{StreamTransport, TokioStream};
+
+/// The protocol over TCP, for devices on the network rather than USB. Every command except the
+/// session handshake needs an authenticated session, see [super::auth].
+pub type TcpTransport = StreamTransport<TcpStream>;
+
+/// Connects to the device at `addr`, usually on
+/// [DEFAULT_TCP_PORT](axis_protocol::transport::DEFAULT_TCP_PORT).
+pub async fn connect(addr: impl ToSocketAddrs) -> io::Result<TcpTransport> {
+    let stream = TcpStream::connect(addr).await?;
+    // Packets are small and latency matters more than throughput
+    stream.set_nodelay(true)?;
+    Ok(FramedStream::new(TokioStream(stream)))
+}
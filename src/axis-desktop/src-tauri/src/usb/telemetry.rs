@@ -0,0 +1,44 @@
+use axis_protocol::delta::DeltaDecoder;
+use axis_protocol::messages::{Commands, Messages};
+use axis_protocol::telemetry::{TelemetryEncoding, TelemetryFrame, Topic};
+
+/// How often the device samples telemetry for the frontend's charts.
+pub const SAMPLE_INTERVAL_MS: u32 = 50;
+
+/// The commands that start the telemetry stream once the device accepted our hello: delta
+/// encoded batches of samples, which take a fraction of the bandwidth of full ones.
+pub fn subscribe() -> [Commands; 2] {
+    [
+        Commands::SetTelemetryEncoding { encoding: TelemetryEncoding::Delta },
+        Commands::Subscribe { topic: Topic::Samples, interval_ms: SAMPLE_INTERVAL_MS },
+    ]
+}
+
+/// Turns telemetry messages into frames, whichever encoding the device sends them in.
+pub struct TelemetryReader {
+    decoder: DeltaDecoder,
+}
+
+impl TelemetryReader {
+    pub fn new() -> Self {
+        Self {
+            decoder: DeltaDecoder::new(),
+        }
+    }
+
+    /// The samples carried by `message`, or `None` if it is not a telemetry batch or could not
+    /// be decoded. After a missed or malformed frame, decoding resumes at the next keyframe.
+    pub fn read(&mut self, message: Messages) -> Option<TelemetryFrame> {
+        match message {
+            Messages::Telemetry(frame) => Some(frame),
+            Messages::CompactTelemetry(compact) => self.decoder.decode(&compact).ok(),
+            _ => None,
+        }
+    }
+
+    /// Forgets the previous frame, as the device starts over with a keyframe on every new
+    /// connection.
+    pub fn reset(&mut self) {
+        self.decoder = DeltaDecoder::new();
+    }
+}
@@ -0,0 +1,49 @@
+use std::io;
+
+use axis_protocol::fragment::MAX_FRAGMENT_LEN;
+use axis_protocol::framing;
+use axis_protocol::transport::FramedStream;
+use embedded_io_async::{ErrorType, Read, Write};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_serial::{SerialPortBuilderExt, SerialStream};
+
+/// Largest frame on either link. Both sides split larger packets into fragments that fit a
+/// single USB packet, see [PacketCodec::split](axis_protocol::codec::PacketCodec::split).
+pub const MAX_FRAME_LEN: usize = framing::max_frame_len(MAX_FRAGMENT_LEN);
+
+/// CDC-ACM ignores the baud rate, but a serial port cannot be opened without one.
+const BAUD_RATE: u32 = 115_200;
+
+/// The protocol over a tokio byte stream.
+pub type StreamTransport<S> = FramedStream<TokioStream<S>, MAX_FRAME_LEN>;
+
+/// The protocol over the device's USB serial port.
+pub type SerialTransport = StreamTransport<SerialStream>;
+
+pub fn open_serial(path: &str) -> Result<SerialTransport, tokio_serial::Error> {
+    let port = tokio_serial::new(path, BAUD_RATE).open_native_async()?;
+    Ok(FramedStream::new(TokioStream(port)))
+}
+
+/// A tokio stream as an `embedded-io-async` byte stream.
+pub struct TokioStream<S>(pub S);
+
+impl<S> ErrorType for TokioStream<S> {
+    type Error = io::Error;
+}
+
+impl<S: AsyncRead + Unpin> Read for TokioStream<S> {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf).await
+    }
+}
+
+impl<S: AsyncWrite + Unpin> Write for TokioStream<S> {
+    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf).await
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        self.0.flush().await
+    }
+}
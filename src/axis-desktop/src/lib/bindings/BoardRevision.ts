@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type BoardRevision = "Unknown" | "V2" | "V2Hv" | "McuV3" | "McuV4" | "BaseRev01" | "GcpHat";
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Sensors and actuators fitted to a board, and optional features its firmware implements.
+ * Clients should not offer what is missing, the host (MCU) answers such commands with
+ * [CommandError::Unsupported](crate::messages::CommandError::Unsupported).
+ */
+export type Capabilities = number;
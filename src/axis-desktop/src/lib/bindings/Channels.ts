@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * One reading of every channel. Channels the board cannot measure are `NaN`, which becomes
+ * `null` in JSON.
+ */
+export type Channels = { boiler: number | null, pressure: number | null, flow: number | null, 
+/**
+ * Pump output, 0 to 100 %.
+ */
+pump_duty: number | null, 
+/**
+ * Heater output, 0 to 100 %.
+ */
+heater_duty: number | null, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Part of the image, starting at `offset` bytes into it.
+ */
+export type Chunk = { offset: number, data: Array<number>, };
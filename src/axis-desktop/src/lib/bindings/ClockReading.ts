@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Answer to [Commands::ReadClock](crate::messages::Commands::ReadClock).
+ */
+export type ClockReading = { 
+/**
+ * Seconds since the Unix epoch, or `None` if the RTC was never set, lost power or does
+ * not respond.
+ */
+unix_time: number | null, 
+/**
+ * Microseconds since the host (MCU) booted, when the time was read.
+ */
+uptime_us: number, };
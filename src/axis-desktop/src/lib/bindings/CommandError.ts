@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AuthError } from "./AuthError";
+import type { ClockError } from "./ClockError";
+import type { HistoryError } from "./HistoryError";
+import type { ParamError } from "./ParamError";
+import type { UpdateError } from "./UpdateError";
+
+/**
+ * Reasons the host (MCU) can reject a command
+ */
+export type CommandError = "Unsupported" | "InvalidArgument" | "Busy" | "HandshakeRequired" | "IncompatibleProtocol" | { "Param": ParamError } | { "Update": UpdateError } | { "Auth": AuthError } | { "History": HistoryError } | { "Clock": ClockError };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Celsius } from "./Celsius";
+import type { Chunk } from "./Chunk";
+import type { Hello } from "./Hello";
+import type { LogLevel } from "./LogLevel";
+import type { ParamValue } from "./ParamValue";
+import type { TelemetryEncoding } from "./TelemetryEncoding";
+import type { Topic } from "./Topic";
+
+/**
+ * Requests sent by the client to the host (MCU).
+ *
+ * Every command is answered with exactly one [Response] carrying the sequence number of the
+ * [MessageHeader](crate::MessageHeader) the command was sent with.
+ *
+ * `Hello` must stay the first variant so that every protocol version can decode it.
+ */
+export type Commands = { "Hello": Hello } | "Ping" | { "SetSetpoint": { setpoint: Celsius, } } | "StartShot" | "StopShot" | "ReadConfig" | "ListParams" | { "DescribeParam": { id: number, } } | { "GetParam": { id: number, } } | { "SetParam": { id: number, value: ParamValue, } } | { "Subscribe": { topic: Topic, interval_ms: number, } } | { "Unsubscribe": { topic: Topic, } } | { "SetLogLevel": { level: LogLevel | null, } } | { "BeginUpdate": { image_len: number, } } | { "WriteChunk": Chunk } | { "VerifyUpdate": { sha256: [number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number], } } | "CommitUpdate" | "AbortUpdate" | { "SetTelemetryEncoding": { encoding: TelemetryEncoding, } } | "ReadDiagnostics" | { "ProvisionKey": { key: [number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number] | null, } } | { "Challenge": { client_nonce: [number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number], } } | { "Authenticate": { proof: [number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number], } } | { "ListShots": { after: number | null, } } | { "ReadShot": { id: number, offset: number, } } | { "AcknowledgeShot": { id: number, } } | { "DeleteShot": { id: number, } } | "ReadClock" | { "SetClock": { unix_time: number, } } | { "SyncClock": { client_time_us: number, } };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A [TelemetryFrame] in compact form.
+ */
+export type CompactFrame = { 
+/**
+ * Counts up by one for every frame, so the receiver notices a missing one.
+ */
+sequence: number, 
+/**
+ * The first sample is stored as is rather than relative to the previous frame.
+ */
+keyframe: boolean, 
+/**
+ * Microseconds since the host (MCU) booted, at the first sample.
+ */
+base_timestamp_us: number, data: Array<number>, };
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Number of decode errors seen in each category, and of packets the sender had to drop, for
+ * diagnostics.
+ */
+export type DecodeStats = { truncated: number, unknown_message_id: number, bad_crc: number, oversized: number, version_mismatch: number, malformed: number, unauthenticated: number, 
+/**
+ * Outbound packets dropped before they were completely sent, e.g. because the peer stopped
+ * reading in the middle of a fragmented transfer. Not a decode error, so not part of
+ * [total](Self::total).
+ */
+send_dropped: number, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BoardRevision } from "./BoardRevision";
+import type { Capabilities } from "./Capabilities";
+import type { FirmwareVersion } from "./FirmwareVersion";
+import type { ProtocolVersion } from "./ProtocolVersion";
+import type { SerialNumber } from "./SerialNumber";
+
+/**
+ * The host (MCU)'s answer to [Hello]. New fields may only be appended, so older clients can
+ * still decode the fields they know.
+ */
+export type DeviceInfo = { protocol: ProtocolVersion, firmware: FirmwareVersion, board: BoardRevision, capabilities: Capabilities, serial: SerialNumber, };
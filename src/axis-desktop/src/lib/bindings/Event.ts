@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EventKind } from "./EventKind";
+import type { Severity } from "./Severity";
+
+/**
+ * Something noteworthy that happened on the host (MCU), pushed to the client as
+ * [Messages::Event](crate::messages::Messages::Event)
+ */
+export type Event = { 
+/**
+ * Stable identifier of the event kind, see [EventKind::code].
+ */
+code: number, severity: Severity, 
+/**
+ * Microseconds since the host (MCU) booted.
+ */
+timestamp_us: number, kind: EventKind, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Celsius } from "./Celsius";
+import type { I2cDevice } from "./I2cDevice";
+import type { Mode } from "./Mode";
+import type { ThermocoupleFault } from "./ThermocoupleFault";
+
+/**
+ * Codes are grouped by subsystem in blocks of 100 and must never be reused once assigned.
+ */
+export type EventKind = { "ThermocoupleFault": ThermocoupleFault } | { "I2cFailure": { device: I2cDevice, } } | { "OverTemperature": { temperature: Celsius, limit: Celsius, } } | "ShotStarted" | { "ShotStopped": { duration_ms: number, } } | { "ModeChanged": { from: Mode, to: Mode, } };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Bar } from "./Bar";
+import type { Grams } from "./Grams";
+import type { Milliliters } from "./Milliliters";
+
+export type ExitCondition = { "Time": { ms: number, } } | { "PressureAbove": Bar } | { "PressureBelow": Bar } | { "Volume": Milliliters } | { "Weight": Grams };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ProtocolVersion } from "./ProtocolVersion";
+
+/**
+ * Sent by the client to open a session.
+ */
+export type Hello = { protocol: ProtocolVersion, };
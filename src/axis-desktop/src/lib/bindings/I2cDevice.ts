@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type I2cDevice = "Ads1119" | "Ds3231m" | "Fm24cl16b" | "Pca9544a" | "Tca9534";
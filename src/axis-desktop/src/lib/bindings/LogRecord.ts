@@ -0,0 +1,31 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LogLevel } from "./LogLevel";
+
+/**
+ * A single log line from the host (MCU).
+ *
+ * ```
+ * use axis_protocol::logs::{LogLevel, LogRecord, MAX_TEXT_LEN};
+ *
+ * let record = LogRecord::new(1_000, LogLevel::Warn, "axis_embedded::link", format_args!("lost {}", 3));
+ * assert_eq!(record.text, "lost 3");
+ *
+ * let long = "x".repeat(200);
+ * let record = LogRecord::new(1_000, LogLevel::Info, "axis", format_args!("{long}"));
+ * assert!(record.truncated);
+ * assert_eq!(record.text.len(), MAX_TEXT_LEN);
+ * ```
+ */
+export type LogRecord = { 
+/**
+ * Microseconds since the host (MCU) booted.
+ */
+timestamp_us: number, level: LogLevel, module: string, text: string, 
+/**
+ * Whether `text` was cut off at [MAX_TEXT_LEN].
+ */
+truncated: boolean, 
+/**
+ * Records dropped by the host (MCU) since the previous one was sent.
+ */
+dropped: number, };
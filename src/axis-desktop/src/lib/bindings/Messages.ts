@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Bar } from "./Bar";
+import type { Celsius } from "./Celsius";
+import type { CompactFrame } from "./CompactFrame";
+import type { Event } from "./Event";
+import type { LogRecord } from "./LogRecord";
+import type { TelemetryFrame } from "./TelemetryFrame";
+
+/**
+ * Events created by the host (MCU) to send back to the client
+ */
+export type Messages = { "ThermocoupleReadout": { temperature: Celsius, } } | { "Event": Event } | { "PressureReadout": { pressure: Bar, } } | { "RtcTime": { hour: number, minute: number, second: number, } } | { "Telemetry": TelemetryFrame } | { "Log": LogRecord } | { "CompactTelemetry": CompactFrame };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Operating mode of the machine
+ */
+export type Mode = "Standby" | "Heating" | "Ready" | "Brewing" | "Steaming" | "Fault";
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ParamValue } from "./ParamValue";
+import type { Unit } from "./Unit";
+
+/**
+ * A [ParamDescriptor] as sent to the client in reply to
+ * [Commands::DescribeParam](crate::messages::Commands::DescribeParam).
+ */
+export type ParamInfo = { id: number, name: string, unit: Unit, min: ParamValue, max: ParamValue, default: ParamValue, persist: boolean, };
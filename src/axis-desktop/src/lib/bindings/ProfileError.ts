@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Why a profile cannot be run. `stage` is the index of the offending stage.
+ */
+export type ProfileError = "Empty" | { "NoExitCondition": { stage: number, } } | { "OutOfRange": { stage: number, } };
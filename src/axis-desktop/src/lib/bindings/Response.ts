@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Responses } from "./Responses";
+
+/**
+ * Reply to a [Commands] request, sent by the host (MCU) to the client
+ */
+export type Response = { 
+/**
+ * Sequence number of the request this is a reply to.
+ */
+sequence: number, body: Responses, };
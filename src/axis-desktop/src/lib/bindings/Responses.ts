@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Celsius } from "./Celsius";
+import type { ClockReading } from "./ClockReading";
+import type { CommandError } from "./CommandError";
+import type { DecodeStats } from "./DecodeStats";
+import type { DeviceInfo } from "./DeviceInfo";
+import type { ParamInfo } from "./ParamInfo";
+import type { ParamValue } from "./ParamValue";
+import type { SamplePage } from "./SamplePage";
+import type { ShotPage } from "./ShotPage";
+
+/**
+ * `Hello` must stay the first variant so that every protocol version can decode it.
+ */
+export type Responses = { "Hello": DeviceInfo } | "Pong" | "Ok" | { "Error": CommandError } | { "Config": { setpoint: Celsius, } } | { "ParamList": Array<number> } | { "ParamInfo": ParamInfo } | { "ParamValue": { id: number, value: ParamValue, } } | { "DecodeStats": DecodeStats } | { "Challenge": { host_nonce: [number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number], } } | { "Authenticated": { proof: [number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number, number], } } | { "ShotList": ShotPage } | { "ShotSamples": SamplePage } | { "Clock": ClockReading } | { "ClockSync": { client_time_us: number, uptime_us: number, } };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ShotSample } from "./ShotSample";
+
+/**
+ * Answer to [Commands::ReadShot](crate::messages::Commands::ReadShot).
+ */
+export type SamplePage = { id: number, 
+/**
+ * Index of the first sample in this page.
+ */
+offset: number, 
+/**
+ * Number of samples in the whole shot.
+ */
+total: number, samples: Array<ShotSample>, };
@@ -0,0 +1,25 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Identifies one board, taken from the unique ID of its flash chip. The USB serial number is
+ * the same ID written as [hex](Self::hex), so a client can match a port to the machine behind
+ * it before opening it.
+ *
+ * Human readable formats like JSON get the hex string as well, so the desktop can key
+ * profiles and history by it, while postcard sends the 8 bytes.
+ *
+ * ```
+ * use axis_protocol::handshake::SerialNumber;
+ *
+ * let serial = SerialNumber([0xe6, 0x61, 0x38, 0x52, 0x83, 0x5f, 0x2a, 0x2f]);
+ * assert_eq!(serial.hex(&mut [0u8; 16]), "E6613852835F2A2F");
+ * assert_eq!(SerialNumber::parse("e6613852835f2a2f"), Some(serial));
+ * assert_eq!(SerialNumber::parse("12345678"), None);
+ *
+ * assert_eq!(serde_json::to_string(&serial).unwrap(), r#""E6613852835F2A2F""#);
+ * assert_eq!(serde_json::from_str::<SerialNumber>(r#""e6613852835f2a2f""#).unwrap(), serial);
+ * let mut buf = [0u8; 8];
+ * assert_eq!(postcard::to_slice(&serial, &mut buf).unwrap(), &serial.0);
+ * ```
+ */
+export type SerialNumber = string;
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ShotSummary } from "./ShotSummary";
+
+/**
+ * Answer to [Commands::ListShots](crate::messages::Commands::ListShots).
+ */
+export type ShotPage = { shots: Array<ShotSummary>, 
+/**
+ * Whether more shots follow the last one in this page.
+ */
+more: boolean, };
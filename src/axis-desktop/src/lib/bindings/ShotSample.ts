@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Channels } from "./Channels";
+
+export type ShotSample = { 
+/**
+ * Milliseconds since the shot started.
+ */
+offset_ms: number, channels: Channels, 
+/**
+ * Weight in the cup, `NaN` without a scale.
+ */
+weight: number | null, };
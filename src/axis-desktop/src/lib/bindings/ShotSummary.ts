@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ShotSummary = { id: number, 
+/**
+ * Seconds since the Unix epoch the shot started at, if the host (MCU) knew the time.
+ */
+started_at: number | null, duration_ms: number, sample_count: number, 
+/**
+ * Whether the shot was acknowledged by a client.
+ */
+downloaded: boolean, };
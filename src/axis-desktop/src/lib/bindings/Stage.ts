@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Celsius } from "./Celsius";
+import type { ExitCondition } from "./ExitCondition";
+import type { Target } from "./Target";
+import type { Transition } from "./Transition";
+
+export type Stage = { target: Target, 
+/**
+ * How the target is approached from where the previous stage ended.
+ */
+transition: Transition, 
+/**
+ * Time the transition takes. Ignored for [Transition::Step].
+ */
+transition_ms: number, temperature: Celsius, 
+/**
+ * The stage ends as soon as any of these is met.
+ */
+exit: Array<ExitCondition>, };
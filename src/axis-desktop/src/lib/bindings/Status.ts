@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceInfo } from "./DeviceInfo";
+
+/**
+ * What the frontend is told about the device connection.
+ */
+export type Status = { "state": "disconnected" } | { "state": "connecting", port: string, } | { "state": "connected", info: DeviceInfo, degraded: boolean, } | { "state": "incompatible", info: DeviceInfo, } | { "state": "failed", reason: string, };
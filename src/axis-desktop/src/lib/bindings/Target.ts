@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Bar } from "./Bar";
+import type { MillilitersPerSecond } from "./MillilitersPerSecond";
+
+/**
+ * What the pump is controlled by during a stage.
+ */
+export type Target = { "Pressure": Bar } | { "Flow": MillilitersPerSecond } | { "PumpPower": number };
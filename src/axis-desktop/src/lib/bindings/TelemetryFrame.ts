@@ -0,0 +1,32 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TelemetrySample } from "./TelemetrySample";
+
+/**
+ * A batch of samples sharing one device timestamp, so bursty delivery does not distort the
+ * time series.
+ *
+ * ```
+ * use axis_protocol::telemetry::{Channels, TelemetryFrame};
+ * use axis_protocol::units::{Bar, Celsius, MillilitersPerSecond};
+ *
+ * let channels = Channels {
+ *     boiler: Celsius(93.0),
+ *     pressure: Bar(9.0),
+ *     flow: MillilitersPerSecond(2.0),
+ *     pump_duty: 80.0,
+ *     heater_duty: f32::NAN,
+ * };
+ *
+ * let mut frame = TelemetryFrame::new();
+ * frame.push(5_000_000, channels).unwrap();
+ * frame.push(5_010_000, channels).unwrap();
+ *
+ * let timestamps: Vec<u64> = frame.iter().map(|(t, _)| t).collect();
+ * assert_eq!(timestamps, [5_000_000, 5_010_000]);
+ * ```
+ */
+export type TelemetryFrame = { 
+/**
+ * Microseconds since the host (MCU) booted, at the first sample.
+ */
+base_timestamp_us: number, samples: Array<TelemetrySample>, };
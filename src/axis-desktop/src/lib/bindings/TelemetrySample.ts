@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Channels } from "./Channels";
+
+export type TelemetrySample = { 
+/**
+ * Microseconds after the frame's `base_timestamp_us` the sample was taken at.
+ */
+offset_us: number, channels: Channels, };
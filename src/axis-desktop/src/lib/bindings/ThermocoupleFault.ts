@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Fault bits reported by a MAX31855 thermocouple converter
+ */
+export type ThermocoupleFault = { short_to_vcc: boolean, short_to_gnd: boolean, open_circuit: boolean, };
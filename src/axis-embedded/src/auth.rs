@@ -0,0 +1,63 @@
+use core::cell::Cell;
+
+use axis_protocol::auth::{AuthError, Authenticator, Key, Nonce, Proof, Session};
+use embassy_rp::clocks::RoscRng;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use rand::RngCore;
+
+/// Pre-shared key for authenticated sessions, shared by every link. Unlike the parameters, the
+/// key is not stored in the FRAM and has to be provisioned again after a reset.
+static KEY: Mutex<CriticalSectionRawMutex, Cell<Option<Key>>> = Mutex::new(Cell::new(None));
+
+/// Sets or removes the pre-shared key. Only to be called for commands received over USB.
+pub fn provision(key: Option<Key>) {
+    KEY.lock(|k| k.set(key));
+    crate::forward!(info, "Session key {}", if key.is_some() { "provisioned" } else { "removed" });
+}
+
+/// One link's side of the session handshake. The key is shared, while the pending challenge
+/// belongs to the link, so a handshake on one link cannot overwrite another's.
+pub struct LinkAuthenticator {
+    authenticator: Authenticator,
+    /// The key `authenticator` was last provisioned with.
+    key: Option<Key>,
+}
+
+impl LinkAuthenticator {
+    pub const fn new() -> Self {
+        Self {
+            authenticator: Authenticator::new(),
+            key: None,
+        }
+    }
+
+    /// Picks up a key provisioned since the last call, which cancels a pending challenge.
+    fn sync_key(&mut self) {
+        let key = KEY.lock(|k| k.get());
+        if key != self.key {
+            self.key = key;
+            self.authenticator.provision(key);
+        }
+    }
+
+    /// Answers [Commands::Challenge](axis_protocol::messages::Commands::Challenge) with a fresh
+    /// random nonce.
+    pub fn challenge(&mut self, client_nonce: Nonce) -> Result<Nonce, AuthError> {
+        self.sync_key();
+        let mut host_nonce = [0u8; 16];
+        RoscRng.fill_bytes(&mut host_nonce);
+        self.authenticator.challenge(client_nonce, host_nonce)
+    }
+
+    /// Answers [Commands::Authenticate](axis_protocol::messages::Commands::Authenticate),
+    /// returning the session to seal the transport with once the answer is sent.
+    pub fn authenticate(&mut self, proof: &Proof) -> Result<(Proof, Session), AuthError> {
+        self.sync_key();
+        let result = self.authenticator.authenticate(proof);
+        if let Err(e) = result {
+            crate::forward!(warn, "Authentication failed: {:?}", e);
+        }
+        result
+    }
+}
@@ -1,34 +1,95 @@
 use core::any::Any;
+use core::cell::RefCell;
 use crate::{MessageType};
 use byte_slice_cast::AsByteSlice;
 
-use defmt::{debug, error};
-use embassy_futures::select::{select, Either};
+use defmt::Format;
+use embassy_futures::select::{select3, select4, Either3, Either4};
 use embassy_rp::bind_interrupts;
 use embassy_rp::peripherals::USB;
 use embassy_rp::usb::Driver;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
-use embassy_sync::channel::{Channel, Receiver, Sender};
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+use embassy_sync::channel::{Channel, Receiver};
 use embassy_sync::mutex::Mutex;
-use embassy_time::{Duration, Timer};
+use embassy_sync::pubsub::WaitResult;
+use embassy_time::{with_timeout, Duration, Instant, Timer};
 use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
 
 use embassy_usb::{Builder, UsbDevice};
-use embassy_usb::driver::EndpointError;
 use heapless::String;
 use static_cell::make_static;
-use axis_protocol::MessageHeader;
-use axis_protocol::messages::Messages;
+use axis_protocol::{MessageHeader, MessageId};
+use axis_protocol::auth::{Opener, Sealer, SEAL_OVERHEAD};
+use axis_protocol::decode::{self, Body, DecodeError, DecodeStats, Decoded, Packet};
+use axis_protocol::fragment::{self, Fragments, Reassembler, MAX_FRAGMENT_LEN};
+use axis_protocol::framing;
+use axis_protocol::messages::{Messages, Response};
+use axis_protocol::reliability::{self, Deduplicator};
+use axis_protocol::heartbeat::{self, Heartbeat, HEARTBEAT_INTERVAL_MS};
+use axis_protocol::transport::{FramedStream, Transport, TransportError};
+use crate::dispatcher::{Dispatcher, Origin};
+use crate::events::{EventSubscriber, EVENTS};
+use crate::logs::{self, LogSubscriber, LOGS};
+use crate::telemetry;
+use crate::link;
+use crate::transport::{CdcStream, UsbTransport};
 
 pub const MAX_PACKET_SIZE: usize = 64;
+/// Largest serialized header and message, sent as fragments when it does not fit into a single
+/// frame.
+pub const MAX_MESSAGE_SIZE: usize = 512;
+/// Largest packet once sealed in an authenticated session.
+const MAX_SEALED_SIZE: usize = MAX_MESSAGE_SIZE + SEAL_OVERHEAD;
+pub const MAX_FRAME_SIZE: usize = framing::max_frame_len(MAX_FRAGMENT_LEN);
+
+/// Inbound frames and packets dropped since boot by reason, and outbound packets dropped.
+static DECODE_STATS: BlockingMutex<CriticalSectionRawMutex, RefCell<DecodeStats>> =
+    BlockingMutex::new(RefCell::new(DecodeStats::new()));
+
+pub fn decode_stats() -> DecodeStats {
+    DECODE_STATS.lock(|stats| *stats.borrow())
+}
+
+fn record_decode_error(error: DecodeError) {
+    DECODE_STATS.lock(|stats| stats.borrow_mut().record(error));
+    crate::forward!(warn, "Dropped inbound packet: {:?}", error);
+}
+
+fn record_send_dropped() {
+    DECODE_STATS.lock(|stats| stats.borrow_mut().record_send_dropped());
+}
+
+/// How long a single frame may take to go out. A client that stopped reading must not stall the
+/// connection, while a slow one still receives every frame of a long transfer. A frame cut short
+/// by the timeout is ended by the transport before the next one, see
+/// [Transport::send](axis_protocol::transport::Transport::send).
+const FRAME_TIMEOUT: Duration = Duration::from_millis(5);
+
+/// Sends one frame, returning `false` if it was dropped.
+async fn send_frame<T>(transport: &mut T, frame: &[u8]) -> Result<bool, TransportError<T::Error>>
+where
+    T: Transport,
+    T::Error: Format,
+{
+    match with_timeout(FRAME_TIMEOUT, transport.send(frame)).await {
+        Ok(Ok(())) => Ok(true),
+        Ok(Err(TransportError::Frame(e))) => {
+            crate::mirror!(error, "Failed to frame packet: {:?}", e);
+            Ok(false)
+        }
+        Ok(Err(e)) => Err(e),
+        Err(_) => {
+            crate::mirror!(debug, "Failed to send frame, timeout of {:?} exceeded", FRAME_TIMEOUT);
+            Ok(false)
+        }
+    }
+}
 
 bind_interrupts!(pub struct Irqs {
     USBCTRL_IRQ => embassy_rp::usb::InterruptHandler<USB>;
 });
 
-type UsbSender<'a> = embassy_usb::class::cdc_acm::Sender<'a, Driver<'a, USB>>;
-type UsbReceiver<'a> = embassy_usb::class::cdc_acm::Receiver<'a, Driver<'a, USB>>;
-
 pub struct UsbWrapper<'a, const N: usize> {
     inner: Mutex<CriticalSectionRawMutex, UsbWrapperInner<'a, N>>
 }
@@ -39,26 +100,38 @@ pub struct UsbData<'a> {
     pub bos_descriptor: &'a mut [u8; 256],
     pub control_buf: &'a mut [u8; 64],
     pub state: &'a mut State<'a>,
+    /// State of the second CDC-ACM interface, carrying human readable logs.
+    pub log_state: &'a mut State<'a>,
+    /// Reported as the USB serial number, see [device::read_serial_number](crate::device::read_serial_number).
+    pub serial_number: &'a str,
+}
+
+/// Packets queued for the client
+#[derive(Debug, Format)]
+pub enum Outbound {
+    Message(Messages),
+    /// Acknowledges the reliable packet with this sequence number.
+    Ack(u8),
+    Response(Response),
 }
 
 pub struct UsbWrapperInner<'a, const N: usize> {
-    usb_sender: UsbSender<'a>,
-    usb_receiver: UsbReceiver<'a>,
-    channel: Channel<CriticalSectionRawMutex, Messages, N>,
+    transport: UsbTransport<'a>,
+    channel: Channel<CriticalSectionRawMutex, Outbound, N>,
 }
 
 impl<'a, const N: usize> UsbWrapperInner<'a, N> {
     pub fn new<'b>(
         usb: USB,
         data: UsbData<'a>,
-    ) -> (Self, UsbDevice<'a, Driver<'a, USB>>) {
+    ) -> (Self, UsbDevice<'a, Driver<'a, USB>>, CdcAcmClass<'a, Driver<'a, USB>>) {
         // Create the driver, from the HAL.
         let driver = Driver::new(usb, Irqs);
 
         let mut config = embassy_usb::Config::new(0xc0de, 0xcafe);
         config.manufacturer = Some("Axis");
         config.product = Some("Axis MCU");
-        config.serial_number = Some("12345678");
+        config.serial_number = Some(data.serial_number);
         config.max_power = 100;
         config.max_packet_size_0 = MAX_PACKET_SIZE as u8;
 
@@ -78,8 +151,10 @@ impl<'a, const N: usize> UsbWrapperInner<'a, N> {
             data.control_buf,
         );
 
-        // Create classes on the builder.
+        // Create classes on the builder. The protocol comes first, so it keeps the lower
+        // interface numbers and port name.
         let class = CdcAcmClass::new(&mut builder, data.state, MAX_PACKET_SIZE as u16);
+        let log_class = CdcAcmClass::new(&mut builder, data.log_state, MAX_PACKET_SIZE as u16);
 
         let usb = builder.build();
 
@@ -87,84 +162,334 @@ impl<'a, const N: usize> UsbWrapperInner<'a, N> {
 
         (
             Self {
-                usb_sender,
-                usb_receiver,
+                transport: FramedStream::new(CdcStream::new(usb_sender, usb_receiver)),
                 channel: Channel::new()
             },
             usb,
+            log_class,
         )
     }
 
     pub async fn run(&'a mut self) -> ! {
         loop {
-            select(
-                Self::read(&mut self.usb_receiver, self.channel.sender().clone()),
-                Self::write_outgoing_packets(&mut self.usb_sender, self.channel.receiver().clone()),
-            ).await;
+            crate::mirror!(debug, "Waiting for USB connection");
+            self.transport.get_mut().wait_connection().await;
+            crate::mirror!(debug, "Connected to host");
+
+            let error = serve(&mut self.transport, &self.channel.receiver(), Origin::Usb).await;
+            crate::mirror!(error, "USB link lost: {:?}", error);
+            self.transport.reset();
         }
     }
+}
 
-    async fn write_outgoing_packets<'b>(
-        usb_sender: &'b mut UsbSender<'a>,
-        receiver: Receiver<'a, CriticalSectionRawMutex, Messages, N>,
-    ) {
-        let mut buf = [0u8; N];
-        loop {
-            let m = receiver.receive().await;
+/// Runs the protocol over `transport` until the link fails, answering commands and pushing
+/// events, telemetry and logs once the client may receive them. State tied to the connection,
+/// like the hello exchange, the session or partially received transfers, starts fresh with
+/// every call.
+pub async fn serve<T, const N: usize>(
+    transport: &mut T,
+    outbound: &Receiver<'_, CriticalSectionRawMutex, Outbound, N>,
+    origin: Origin,
+) -> TransportError<T::Error>
+where
+    T: Transport,
+    T::Error: Format,
+{
+    let mut connection = Connection::new(transport, origin);
+    let heartbeat_interval = Duration::from_millis(HEARTBEAT_INTERVAL_MS);
+    let mut next_heartbeat = Instant::now() + heartbeat_interval;
+    let mut feeds = None;
+    let error = loop {
+        if feeds.is_none() && connection.dispatcher.may_push() {
+            feeds = Feeds::subscribe();
+        }
+        let push = async {
+            match feeds.as_mut() {
+                Some(feeds) => next_outbound(outbound, feeds, origin).await,
+                None => core::future::pending().await,
+            }
+        };
 
-            debug!("Outbound message: {:?}", m);
-            let slice = postcard::to_slice(&m, &mut buf);
+        let result = match select3(connection.transport.receive(), push, Timer::at(next_heartbeat)).await {
+            Either3::First(Ok(packet)) => match decode(packet, &mut connection.reassembler, connection.opener.as_mut()) {
+                Ok(Some(packet)) => connection.handle_packet(packet).await,
+                Ok(None) => Ok(()),
+                Err(e) => {
+                    record_decode_error(e);
+                    Ok(())
+                }
+            },
+            Either3::First(Err(TransportError::Frame(e))) => {
+                record_decode_error(e.into());
+                Ok(())
+            }
+            Either3::First(Err(e)) => Err(e),
+            Either3::Second(Some(outbound)) => connection.write(outbound).await,
+            Either3::Second(None) => Ok(()),
+            Either3::Third(_) => {
+                next_heartbeat = Instant::now() + heartbeat_interval;
+                connection.write_heartbeat().await
+            }
+        };
 
-            let Ok(ser) = slice else {
-                debug!("Failed to serialize message: {:?}", m);
-                continue;
-            };
+        if let Err(e) = result {
+            break e;
+        }
+    };
 
-            let duration = Duration::from_millis(5);
-            let timeout = Timer::after(duration);
-            match select(usb_sender.write_packet(ser), timeout).await {
-                Either::First(_) => {}
-                Either::Second(_) => {
-                    debug!("Failed to send message over USB, timeout exceeded. Message: {:?}, Timeout: {:?}", m, duration);
-                }
-            };
+    if let Err(e) = connection.reassembler.abort() {
+        record_decode_error(e.into());
+    }
+    telemetry::unsubscribe_all(origin);
+    logs::reset_level(origin);
+    error
+}
+
+/// A link's subscriptions to the events and logs every link receives.
+struct Feeds {
+    events: EventSubscriber,
+    logs: LogSubscriber,
+    /// Records this link missed by falling behind, reported with the next one it gets.
+    logs_lagged: u64,
+}
+
+impl Feeds {
+    /// Returns `None` if every subscriber slot is taken, which only happens when a link
+    /// outlives its [serve] call.
+    fn subscribe() -> Option<Self> {
+        Some(Self {
+            events: EVENTS.subscriber().ok()?,
+            logs: LOGS.subscriber().ok()?,
+            logs_lagged: 0,
+        })
+    }
+}
+
+/// The next packet to push to the client on `origin`, from the link's own queue, its telemetry
+/// or the events and logs shared by every link. Returns `None` when the link fell behind on
+/// events, after reporting the gap.
+async fn next_outbound<const N: usize>(
+    outbound: &Receiver<'_, CriticalSectionRawMutex, Outbound, N>,
+    feeds: &mut Feeds,
+    origin: Origin,
+) -> Option<Outbound> {
+    let events = feeds.events.next_message();
+    let logs = feeds.logs.next_message();
+    match select4(outbound.receive(), events, telemetry::queue(origin).receive(), logs).await {
+        Either4::First(outbound) => Some(outbound),
+        Either4::Second(WaitResult::Message(event)) => Some(Outbound::Message(Messages::Event(event))),
+        Either4::Second(WaitResult::Lagged(missed)) => {
+            crate::forward!(warn, "Fell behind on events, {} were not sent", missed);
+            None
+        }
+        Either4::Third(message) => Some(Outbound::Message(message)),
+        Either4::Fourth(WaitResult::Message(record)) if !logs::enabled(origin, record.level) => {
+            // The drops it reports are still news to this link
+            feeds.logs_lagged += record.dropped as u64;
+            None
+        }
+        Either4::Fourth(WaitResult::Message(mut record)) => {
+            let missed = core::mem::take(&mut feeds.logs_lagged);
+            record.dropped = record.dropped.saturating_add(missed.min(u16::MAX as u64) as u16);
+            Some(Outbound::Message(Messages::Log(record)))
+        }
+        Either4::Fourth(WaitResult::Lagged(missed)) => {
+            feeds.logs_lagged += missed;
+            None
         }
     }
+}
 
-    async fn read<'b>(
-        usb_receiver: &'b mut UsbReceiver<'a>,
-        sender: Sender<'a, CriticalSectionRawMutex, Messages, N>,
-    ) {
-        let mut buff = [0u8; N];
-        debug!("Waiting for USB connection");
-        usb_receiver.wait_connection().await;
-        debug!("Connected to host");
-        loop {
-            let res = usb_receiver.read_packet(&mut buff[..]).await;
+/// Decodes a packet taken out of a frame, reassembling fragments and opening sealed packets.
+/// Returns `Ok(None)` while a transfer is still incomplete.
+fn decode(
+    packet: &[u8],
+    reassembler: &mut Reassembler<MAX_SEALED_SIZE>,
+    opener: Option<&mut Opener>,
+) -> Result<Option<Packet>, DecodeError> {
+    let decoded = match decode::decode_packet(packet)? {
+        Decoded::Fragment { header, body } => match reassembler.push(header, body)? {
+            Some(packet) => decode::decode_packet(packet)?,
+            None => return Ok(None),
+        },
+        decoded => decoded,
+    };
+    decode::open(opener, decoded).map(Some)
+}
 
-            let s = match res {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("Error reading packet: {:?}", e);
-                    continue;
+/// State of one connection to the client.
+struct Connection<'t, T> {
+    transport: &'t mut T,
+    dispatcher: Dispatcher,
+    /// Set once the client authenticated, after which every packet is sealed both ways.
+    sealer: Option<Sealer>,
+    opener: Option<Opener>,
+    sequence: u8,
+    transfer: u8,
+    deduplicator: Deduplicator,
+    reassembler: Reassembler<MAX_SEALED_SIZE>,
+    packet_buf: [u8; MAX_MESSAGE_SIZE],
+    sealed_buf: [u8; MAX_SEALED_SIZE],
+    fragment_buf: [u8; MAX_FRAGMENT_LEN],
+}
+
+impl<'t, T> Connection<'t, T>
+where
+    T: Transport,
+    T::Error: Format,
+{
+    fn new(transport: &'t mut T, origin: Origin) -> Self {
+        Self {
+            transport,
+            dispatcher: Dispatcher::new(origin),
+            sealer: None,
+            opener: None,
+            sequence: 0,
+            transfer: 0,
+            deduplicator: Deduplicator::new(),
+            reassembler: Reassembler::new(),
+            packet_buf: [0u8; MAX_MESSAGE_SIZE],
+            sealed_buf: [0u8; MAX_SEALED_SIZE],
+            fragment_buf: [0u8; MAX_FRAGMENT_LEN],
+        }
+    }
+
+    /// Handles a packet from the client, acknowledging and answering commands.
+    async fn handle_packet(&mut self, packet: Packet) -> Result<(), TransportError<T::Error>> {
+        // Anyone on the network could otherwise keep the machine out of failsafe
+        if self.dispatcher.is_trusted() {
+            link::host_seen();
+        }
+
+        // Heartbeats count too, so the window follows the client's counter across wraps
+        let first = self.deduplicator.accept(packet.sequence);
+        match packet.body {
+            Body::Heartbeat(_) => Ok(()),
+            Body::Command(command) => {
+                // Ack duplicates too, the client resends when our previous ack got lost
+                let sequence = packet.sequence;
+                self.write(Outbound::Ack(sequence)).await?;
+                if !first {
+                    crate::mirror!(debug, "Dropped duplicate command {}", sequence);
+                    return Ok(());
                 }
-            };
 
-            debug!("Read data: {:?}", buff);
+                let (body, session) = self.dispatcher.dispatch(command).await;
+                self.write(Outbound::Response(Response::new(sequence, body))).await?;
+                // The answer to the proof goes out in the clear, everything after it is sealed
+                if let Some(session) = session {
+                    self.sealer = Some(session.sealer);
+                    self.opener = Some(session.opener);
+                    crate::forward!(info, "Client authenticated");
+                }
+                Ok(())
+            }
+            body => {
+                crate::mirror!(error, "Unexpected message id {:?}", body.message_id() as u8);
+                Ok(())
+            }
+        }
+    }
 
-            let res: postcard::Result<MessageHeader> = postcard::from_bytes(&buff[..3]);
+    /// Every packet sent takes its sequence number from here, acks and heartbeats included, as
+    /// the client's opener requires them to count up.
+    fn next_sequence(&mut self) -> u8 {
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+        sequence
+    }
 
-            let Ok(header) = res else {
-                error!("Failed to deserialize header: {:?}", &buff[..3]);
-                continue;
-            };
+    fn next_header(&mut self, message_id: MessageId) -> MessageHeader {
+        MessageHeader::new(self.next_sequence(), message_id)
+    }
+
+    async fn write_heartbeat(&mut self) -> Result<(), TransportError<T::Error>> {
+        let heartbeat = Heartbeat { uptime_ms: Instant::now().as_millis() as u32 };
+        let sequence = self.next_sequence();
+        let Ok(packet) = heartbeat::encode_heartbeat(sequence, heartbeat, &mut self.packet_buf) else {
+            return Ok(());
+        };
+        let len = packet.len();
+        self.send(len).await
+    }
+
+    async fn write(&mut self, outbound: Outbound) -> Result<(), TransportError<T::Error>> {
+        let packet = match &outbound {
+            Outbound::Ack(acked) => {
+                let sequence = self.next_sequence();
+                reliability::encode_ack(sequence, *acked, &mut self.packet_buf)
+            }
+            Outbound::Message(message) => {
+                axis_protocol::encode_packet(self.next_header(MessageId::Message), message, &mut self.packet_buf)
+            }
+            Outbound::Response(response) => {
+                axis_protocol::encode_packet(self.next_header(MessageId::Response), response, &mut self.packet_buf)
+            }
+        };
+        let Ok(packet) = packet else {
+            crate::mirror!(error, "Failed to serialize {:?}", outbound);
+            record_send_dropped();
+            return Ok(());
+        };
+        let len = packet.len();
 
-            let Ok(message) = postcard::from_bytes(&buff[3..s]) else {
-                error!("Failed to deserialize header: {:?}", &buff[3..s]);
-                continue;
+        crate::mirror!(debug, "Outbound: {:?}", outbound);
+        self.send(len).await
+    }
+
+    /// Sends the first `len` bytes of `packet_buf`, sealed while a session is open and split
+    /// into fragments when they do not fit into one frame. Packets that cannot be sent are
+    /// dropped and counted in [decode_stats], only a failed link is an error.
+    async fn send(&mut self, len: usize) -> Result<(), TransportError<T::Error>> {
+        let packet: &[u8] = match &mut self.sealer {
+            Some(sealer) => match sealer.seal(&self.packet_buf[..len], &mut self.sealed_buf) {
+                Ok(sealed) => sealed,
+                Err(e) => {
+                    crate::mirror!(error, "Failed to seal packet: {:?}", e);
+                    record_send_dropped();
+                    return Ok(());
+                }
+            },
+            None => &self.packet_buf[..len],
+        };
+
+        let fragments = if fragment::needs_fragmenting(packet.len()) {
+            let Ok(fragments) = Fragments::new(packet, self.transfer) else {
+                crate::mirror!(error, "Packet of {} bytes too large to fragment", packet.len());
+                record_send_dropped();
+                return Ok(());
             };
+            self.transfer = self.transfer.wrapping_add(1);
+            Some(fragments)
+        } else {
+            None
+        };
+
+        let transport = &mut *self.transport;
+        let sent = match fragments {
+            Some(mut fragments) => {
+                let mut sent = true;
+                while let Some(fragment) = fragments.next_into(&mut self.fragment_buf) {
+                    let Ok(fragment) = fragment else {
+                        crate::mirror!(error, "Failed to serialize fragment");
+                        sent = false;
+                        break;
+                    };
+                    // The rest of the transfer cannot be reassembled without this fragment
+                    if !send_frame(transport, fragment).await? {
+                        sent = false;
+                        break;
+                    }
+                }
+                sent
+            }
+            None => send_frame(transport, packet).await?,
+        };
 
-            sender.send(message).await;
+        if !sent {
+            record_send_dropped();
         }
+        Ok(())
     }
 }
@@ -0,0 +1,135 @@
+use core::cell::Cell;
+
+use axis_protocol::clock::{ClockError, ClockReading, UtcDateTime, MAX_UNIX_TIME, MIN_UNIX_TIME};
+use axis_protocol::messages::Responses;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Instant;
+
+use crate::drivers::ds3231m::{DateTime, Day, Time};
+
+static STATE: Mutex<CriticalSectionRawMutex, Cell<State>> = Mutex::new(Cell::new(State::new()));
+
+/// Time to write to the RTC, picked up by the task polling it.
+static PENDING: Signal<CriticalSectionRawMutex, u64> = Signal::new();
+
+#[derive(Clone, Copy)]
+struct State {
+    /// Last valid time read from the RTC, with the uptime it was read at.
+    last_read: Option<(u64, Instant)>,
+    responding: bool,
+}
+
+impl State {
+    const fn new() -> Self {
+        Self {
+            last_read: None,
+            responding: true,
+        }
+    }
+}
+
+/// Stores a successful read of the RTC, called by the task polling it.
+pub fn record(date_time: &DateTime) {
+    let unix_time = to_unix(date_time);
+    STATE.lock(|state| {
+        state.set(State {
+            last_read: unix_time.map(|unix_time| (unix_time, Instant::now())),
+            responding: true,
+        })
+    });
+}
+
+/// Marks the RTC as not responding, called by the task polling it.
+pub fn record_failure() {
+    STATE.lock(|state| {
+        state.set(State {
+            last_read: None,
+            responding: false,
+        })
+    });
+}
+
+/// Seconds since the Unix epoch, counted on from the last read, or `None` if the time is not
+/// known.
+pub fn now() -> Option<u64> {
+    let (unix_time, read_at) = STATE.lock(|state| state.get().last_read)?;
+    Some(unix_time + read_at.elapsed().as_secs())
+}
+
+/// Queues `unix_time` to be written to the RTC. Reads return it right away.
+pub fn set(unix_time: u64) -> Result<(), ClockError> {
+    if !(MIN_UNIX_TIME..=MAX_UNIX_TIME).contains(&unix_time) {
+        return Err(ClockError::OutOfRange);
+    }
+    STATE.lock(|state| {
+        let current = state.get();
+        if !current.responding {
+            return Err(ClockError::Unavailable);
+        }
+        state.set(State {
+            last_read: Some((unix_time, Instant::now())),
+            ..current
+        });
+        Ok(())
+    })?;
+
+    crate::forward!(info, "Setting the clock to {}", unix_time);
+    PENDING.signal(unix_time);
+    Ok(())
+}
+
+/// The time waiting to be written to the RTC, if the client set one.
+pub fn take_pending() -> Option<DateTime> {
+    PENDING.try_take().map(from_unix)
+}
+
+/// Answers [Commands::ReadClock](axis_protocol::messages::Commands::ReadClock).
+pub fn read() -> Responses {
+    Responses::Clock(ClockReading {
+        unix_time: now(),
+        uptime_us: Instant::now().as_micros(),
+    })
+}
+
+/// Answers [Commands::SyncClock](axis_protocol::messages::Commands::SyncClock), as soon as the
+/// command arrives so the uptime is taken close to the middle of the round trip.
+pub fn sync(client_time_us: u64) -> Responses {
+    Responses::ClockSync {
+        client_time_us,
+        uptime_us: Instant::now().as_micros(),
+    }
+}
+
+fn to_unix(date_time: &DateTime) -> Option<u64> {
+    if date_time.oscillator_stopped {
+        return None;
+    }
+    UtcDateTime {
+        year: date_time.year,
+        month: date_time.month,
+        day: date_time.date,
+        hour: date_time.time.hour,
+        minute: date_time.time.minute,
+        second: date_time.time.second,
+    }
+    .to_unix()
+}
+
+fn from_unix(unix_time: u64) -> DateTime {
+    let utc = UtcDateTime::from_unix(unix_time);
+    DateTime {
+        time: Time {
+            second: utc.second,
+            minute: utc.minute,
+            hour: utc.hour,
+            am_pm: false,
+        },
+        date: utc.day,
+        day: Day::from_weekday(utc.weekday()),
+        month: utc.month,
+        year: utc.year,
+        oscillator_stopped: false,
+    }
+}
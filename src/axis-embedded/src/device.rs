@@ -0,0 +1,108 @@
+use core::cell::Cell;
+
+use axis_protocol::handshake::{
+    BoardRevision, Capabilities, Compatibility, DeviceInfo, FirmwareVersion, Hello, SerialNumber, PROTOCOL_VERSION,
+};
+use axis_protocol::messages::{CommandError, Responses};
+use embassy_rp::flash::{Blocking, Flash};
+use embassy_rp::peripherals::FLASH;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+
+pub const FIRMWARE_VERSION: FirmwareVersion = FirmwareVersion {
+    major: parse_version(env!("CARGO_PKG_VERSION_MAJOR")),
+    minor: parse_version(env!("CARGO_PKG_VERSION_MINOR")),
+    patch: parse_version(env!("CARGO_PKG_VERSION_PATCH")),
+};
+
+pub const BOARD_REVISION: BoardRevision = BoardRevision::BaseRev01;
+
+/// Leaves out [Capabilities::FIRMWARE_UPDATE] and [Capabilities::SHOT_HISTORY], see
+/// [Dispatcher](crate::dispatcher::Dispatcher).
+pub const CAPABILITIES: Capabilities = Capabilities::BOILER_THERMOCOUPLE
+    .union(Capabilities::PRESSURE_SENSOR)
+    .union(Capabilities::REAL_TIME_CLOCK)
+    .union(Capabilities::FRAM);
+
+/// Size of the QSPI flash chip, see `memory.x`.
+const FLASH_SIZE: usize = 2 * 1024 * 1024;
+
+static SERIAL_NUMBER: Mutex<CriticalSectionRawMutex, Cell<SerialNumber>> =
+    Mutex::new(Cell::new(SerialNumber([0; 8])));
+
+/// Reads the unique ID of the flash chip as this board's serial number. Must run at boot, before
+/// the second core or any DMA touches the flash, as reading the ID briefly disables XIP.
+pub fn read_serial_number(flash: FLASH) -> SerialNumber {
+    let mut flash = Flash::<_, Blocking, FLASH_SIZE>::new_blocking(flash);
+    let mut id = [0u8; 8];
+    if let Err(e) = flash.blocking_unique_id(&mut id) {
+        crate::mirror!(warn, "Failed to read the flash unique ID: {:?}", e);
+    }
+
+    let serial = SerialNumber(id);
+    crate::mirror!(info, "Serial number {:?}", serial);
+    SERIAL_NUMBER.lock(|s| s.set(serial));
+    serial
+}
+
+pub fn device_info() -> DeviceInfo {
+    DeviceInfo {
+        protocol: PROTOCOL_VERSION,
+        firmware: FIRMWARE_VERSION,
+        board: BOARD_REVISION,
+        capabilities: CAPABILITIES,
+        serial: SERIAL_NUMBER.lock(|s| s.get()),
+    }
+}
+
+/// Tracks whether the client has completed the hello exchange.
+pub struct Session {
+    established: bool,
+}
+
+impl Session {
+    pub const fn new() -> Self {
+        Self { established: false }
+    }
+
+    /// Answers a [Hello], establishing the session if the client's protocol is compatible.
+    pub fn hello(&mut self, hello: Hello) -> Responses {
+        match PROTOCOL_VERSION.compatibility(&hello.protocol) {
+            Compatibility::Incompatible => {
+                crate::forward!(warn, "Rejected client with protocol {:?}", hello.protocol);
+                self.established = false;
+                Responses::Error(CommandError::IncompatibleProtocol)
+            }
+            compatibility => {
+                crate::forward!(info, "Client connected with protocol {:?} ({:?})", hello.protocol, compatibility);
+                self.established = true;
+                Responses::Hello(device_info())
+            }
+        }
+    }
+
+    /// Fails every command other than [Hello] until the session is established.
+    pub fn check(&self) -> Result<(), CommandError> {
+        if self.established {
+            Ok(())
+        } else {
+            Err(CommandError::HandshakeRequired)
+        }
+    }
+
+    /// Closes the session, e.g. when the host disconnects.
+    pub fn reset(&mut self) {
+        self.established = false;
+    }
+}
+
+const fn parse_version(s: &str) -> u8 {
+    let bytes = s.as_bytes();
+    let mut value = 0u8;
+    let mut i = 0;
+    while i < bytes.len() {
+        value = value * 10 + (bytes[i] - b'0');
+        i += 1;
+    }
+    value
+}
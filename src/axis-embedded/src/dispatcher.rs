@@ -0,0 +1,193 @@
+use axis_protocol::auth::{AuthError, Proof, Session};
+use axis_protocol::messages::{CommandError, Commands, Responses};
+use axis_protocol::params::{self, ParamError, DESCRIPTORS, MAX_PARAMS};
+use axis_protocol::telemetry::SubscribeError;
+use defmt::Format;
+use heapless::Vec;
+
+use crate::params::PARAMS;
+use crate::systems::control;
+use crate::{auth, client_communicator, clock, device, logs, telemetry};
+
+/// The link a client is connected over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Format)]
+pub enum Origin {
+    /// Physically attached, so trusted to provision keys.
+    Usb,
+    /// Anyone on the network, so every command past the handshake needs an authenticated
+    /// session.
+    Network,
+}
+
+impl Origin {
+    /// Every link, one client each.
+    pub const ALL: [Origin; 2] = [Origin::Usb, Origin::Network];
+
+    /// Position of the link in per-link state, below `ALL.len()`.
+    pub const fn index(self) -> usize {
+        match self {
+            Origin::Usb => 0,
+            Origin::Network => 1,
+        }
+    }
+}
+
+/// Routes the commands of one connected client to the subsystems owning them.
+pub struct Dispatcher {
+    origin: Origin,
+    hello: device::Session,
+    /// The link's own pending challenge, so handshakes on two links cannot interfere.
+    authenticator: auth::LinkAuthenticator,
+    authenticated: bool,
+}
+
+impl Dispatcher {
+    pub const fn new(origin: Origin) -> Self {
+        Self {
+            origin,
+            hello: device::Session::new(),
+            authenticator: auth::LinkAuthenticator::new(),
+            authenticated: false,
+        }
+    }
+
+    /// Runs `command`, returning the response and, once the client authenticated, the session
+    /// to seal the connection with after the response is sent.
+    pub async fn dispatch(&mut self, command: Commands) -> (Responses, Option<Session>) {
+        crate::mirror!(debug, "Dispatching {:?}", command);
+        match command {
+            Commands::Hello(hello) => (self.hello.hello(hello), None),
+            Commands::Authenticate { proof } => self.authenticate(&proof),
+            command => {
+                let response = match self.check(&command) {
+                    Ok(()) => self.run(command).await.unwrap_or_else(Responses::Error),
+                    Err(e) => Responses::Error(e),
+                };
+                (response, None)
+            }
+        }
+    }
+
+    /// Whether events, telemetry and logs may be pushed to the client, which over the network
+    /// also needs an authenticated session.
+    pub fn may_push(&self) -> bool {
+        self.hello.check().is_ok() && self.is_trusted()
+    }
+
+    /// Whether the client may control the machine: always over USB, over the network once it
+    /// authenticated.
+    pub fn is_trusted(&self) -> bool {
+        self.origin == Origin::Usb || self.authenticated
+    }
+
+    /// Fails every command until the hello exchange completed, and over the network every
+    /// command but the challenge until the client authenticated.
+    fn check(&self, command: &Commands) -> Result<(), CommandError> {
+        self.hello.check()?;
+        let challenge = matches!(command, Commands::Challenge { .. });
+        if !self.is_trusted() && !challenge {
+            return Err(CommandError::Auth(AuthError::Required));
+        }
+        Ok(())
+    }
+
+    fn authenticate(&mut self, proof: &Proof) -> (Responses, Option<Session>) {
+        if let Err(e) = self.hello.check() {
+            return (Responses::Error(e), None);
+        }
+        match self.authenticator.authenticate(proof) {
+            Ok((proof, session)) => {
+                self.authenticated = true;
+                (Responses::Authenticated { proof }, Some(session))
+            }
+            Err(e) => (Responses::Error(CommandError::Auth(e)), None),
+        }
+    }
+
+    async fn run(&mut self, command: Commands) -> Result<Responses, CommandError> {
+        let response = match command {
+            Commands::Ping => Responses::Pong,
+            Commands::SetSetpoint { setpoint } => {
+                control::set_setpoint(setpoint).await?;
+                Responses::Ok
+            }
+            Commands::StartShot => {
+                control::start_shot().await?;
+                Responses::Ok
+            }
+            Commands::StopShot => {
+                control::stop_shot().await;
+                Responses::Ok
+            }
+            Commands::ReadConfig => Responses::Config {
+                setpoint: control::setpoint().await,
+            },
+            Commands::ListParams => {
+                let ids: Vec<_, MAX_PARAMS> = DESCRIPTORS.iter().take(MAX_PARAMS).map(|d| d.id).collect();
+                Responses::ParamList(ids)
+            }
+            Commands::DescribeParam { id } => {
+                let descriptor = params::descriptor(id).ok_or(CommandError::Param(ParamError::UnknownParameter))?;
+                Responses::ParamInfo(descriptor.info())
+            }
+            Commands::GetParam { id } => {
+                let value = PARAMS.lock().await.get(id).map_err(CommandError::Param)?;
+                Responses::ParamValue { id, value }
+            }
+            Commands::SetParam { id, value } => {
+                let value = PARAMS.lock().await.set(id, value).map_err(CommandError::Param)?;
+                crate::forward!(info, "Parameter {} set to {:?}", id, value);
+                Responses::ParamValue { id, value }
+            }
+            Commands::Subscribe { topic, interval_ms } => {
+                telemetry::subscribe(self.origin, topic, interval_ms).map_err(|e| match e {
+                    SubscribeError::IntervalTooShort => CommandError::InvalidArgument,
+                })?;
+                Responses::Ok
+            }
+            Commands::Unsubscribe { topic } => {
+                telemetry::unsubscribe(self.origin, topic);
+                Responses::Ok
+            }
+            Commands::SetLogLevel { level } => {
+                logs::set_level(self.origin, level);
+                Responses::Ok
+            }
+            Commands::SetTelemetryEncoding { encoding } => {
+                telemetry::set_encoding(self.origin, encoding);
+                Responses::Ok
+            }
+            Commands::ReadDiagnostics => Responses::DecodeStats(client_communicator::decode_stats()),
+            // Over the network, whoever could provision a key could also take over the machine
+            Commands::ProvisionKey { .. } if self.origin != Origin::Usb => return Err(CommandError::Unsupported),
+            Commands::ProvisionKey { key } => {
+                auth::provision(key);
+                Responses::Ok
+            }
+            Commands::Challenge { client_nonce } => {
+                let host_nonce = self.authenticator.challenge(client_nonce).map_err(CommandError::Auth)?;
+                Responses::Challenge { host_nonce }
+            }
+            Commands::ReadClock => clock::read(),
+            Commands::SetClock { unix_time } => {
+                clock::set(unix_time).map_err(CommandError::Clock)?;
+                Responses::Ok
+            }
+            Commands::SyncClock { client_time_us } => clock::sync(client_time_us),
+            // There is no update partition or shot storage on this board yet, so the handshake
+            // does not advertise them either, see device::CAPABILITIES
+            Commands::BeginUpdate { .. }
+            | Commands::WriteChunk(_)
+            | Commands::VerifyUpdate { .. }
+            | Commands::CommitUpdate
+            | Commands::AbortUpdate
+            | Commands::ListShots { .. }
+            | Commands::ReadShot { .. }
+            | Commands::AcknowledgeShot { .. }
+            | Commands::DeleteShot { .. } => return Err(CommandError::Unsupported),
+            // Handled by dispatch
+            Commands::Hello(_) | Commands::Authenticate { .. } => return Err(CommandError::InvalidArgument),
+        };
+        Ok(response)
+    }
+}
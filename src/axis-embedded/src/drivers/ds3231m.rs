@@ -2,6 +2,11 @@ use bitfield::bitfield;
 use defmt::{info, Format, Formatter};
 use embedded_hal_async::i2c::I2c;
 
+const TIMEKEEPING_REGISTER: u8 = 0x00;
+const STATUS_REGISTER: u8 = 0x0f;
+/// Oscillator stop flag in the status register.
+const OSF: u8 = 1 << 7;
+
 pub struct Ds3231m<I2C: I2c> {
     i2c: I2C,
     address: u8,
@@ -17,7 +22,8 @@ impl<I2C: I2c> Ds3231m<I2C> {
 
     async fn read(&mut self) -> Result<TimekeepingRegisters<[u8; 19]>, I2C::Error> {
         let buf = &mut [0u8; 19];
-        self.i2c.read(self.address, buf).await.map(|_| {
+        // Writes leave the register pointer where they ended, so always start from the top
+        self.i2c.write_read(self.address, &[TIMEKEEPING_REGISTER], buf).await.map(|_| {
             TimekeepingRegisters(*buf)
         })
     }
@@ -25,6 +31,19 @@ impl<I2C: I2c> Ds3231m<I2C> {
     pub async fn read_date_time(&mut self) -> Result<DateTime, I2C::Error> {
         self.read().await.map(|reg| reg.get_date_time())
     }
+
+    /// Sets the time in 24 hour mode and clears the oscillator stop flag, so the time reads as
+    /// valid again.
+    pub async fn write_date_time(&mut self, date_time: &DateTime) -> Result<(), I2C::Error> {
+        let mut buf = [0u8; 8];
+        buf[0] = TIMEKEEPING_REGISTER;
+        TimekeepingRegisters(&mut buf[1..]).set_date_time(date_time);
+        self.i2c.write(self.address, &buf).await?;
+
+        let status = &mut [0u8; 1];
+        self.i2c.write_read(self.address, &[STATUS_REGISTER], status).await?;
+        self.i2c.write(self.address, &[STATUS_REGISTER, status[0] & !OSF]).await
+    }
 }
 
 bitfield! {
@@ -37,6 +56,7 @@ bitfield! {
     minutes_10, set_minutes_10:                 6 +0x01*8, 4 +0x01*8;
     minutes, set_minutes:                       3 +0x01*8, 0 +0x01*8;
 
+    hours_12_24, set_hours_12_24:               6 +0x02*8, 6 +0x02*8;
     hours_20, set_hours_20:                     5 +0x02*8, 5 +0x02*8;
     hours_am_pm, set_hours_am_pm:               5 +0x02*8, 5 +0x02*8;
     hours_10, set_hours_10:                     4 +0x02*8, 4 +0x02*8;
@@ -119,39 +139,68 @@ impl<T: AsRef<[u8]>> TimekeepingRegisters<T> {
             time: self.get_time(),
             date: self.get_date(),
             day: self.get_day(),
+            month: self.get_month(),
+            year: self.get_year(),
+            oscillator_stopped: self.osf() > 0,
         }
     }
 
+    /// The time of day, with `hour` counted from 0 to 23 in either mode.
     pub fn get_time(&self) -> Time {
-        let use_am_pm = self.hours_am_pm() > 0;
+        let use_am_pm = self.hours_12_24() > 0;
+        let hour = self.hours() + (10 * self.hours_10());
         Time {
             second: self.seconds() + (self.seconds_10() * 10),
             minute: self.minutes() + (self.minutes_10() * 10),
             am_pm: use_am_pm,
-            hour: self.hours() + (10 * self.hours_10()) +
-                (match use_am_pm {
-                    true => 0,
-                    false => 20 * self.hours_20()
-            })
+            hour: match use_am_pm {
+                true => hour % 12 + 12 * self.hours_am_pm(),
+                false => hour + 20 * self.hours_20(),
+            },
         }
     }
 
     pub fn get_day(&self) -> Day {
-        match self.day_day() {
-            1 => Day::Monday,
-            2 => Day::Tuesday,
-            3 => Day::Wednesday,
-            4 => Day::Thursday,
-            5 => Day::Friday,
-            6 => Day::Saturday,
-            7 => Day::Sunday,
-            _ => Day::None,
-        }
+        Day::from_weekday(self.day_day())
     }
 
     pub fn get_date(&self) -> u8 {
         self.date() + (10 * self.date_10())
     }
+
+    pub fn get_month(&self) -> u8 {
+        self.month() + (10 * self.month_10())
+    }
+
+    pub fn get_year(&self) -> u16 {
+        2000 + 100 * self.century() as u16 + (self.year() + 10 * self.year_10()) as u16
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> TimekeepingRegisters<T> {
+    /// Fills in the first 7 registers, in 24 hour mode. `date_time.time.am_pm` is ignored.
+    pub fn set_date_time(&mut self, date_time: &DateTime) {
+        let time = &date_time.time;
+        self.set_seconds_10(time.second / 10);
+        self.set_seconds(time.second % 10);
+        self.set_minutes_10(time.minute / 10);
+        self.set_minutes(time.minute % 10);
+        self.set_hours_12_24(0);
+        self.set_hours_20(time.hour / 20);
+        self.set_hours_10(time.hour % 20 / 10);
+        self.set_hours(time.hour % 10);
+
+        self.set_day_day(date_time.day as u8);
+        self.set_date_10(date_time.date / 10);
+        self.set_date(date_time.date % 10);
+        self.set_month_10(date_time.month / 10);
+        self.set_month(date_time.month % 10);
+
+        let year = date_time.year.saturating_sub(2000);
+        self.set_century((year / 100) as u8);
+        self.set_year_10((year % 100 / 10) as u8);
+        self.set_year((year % 10) as u8);
+    }
 }
 
 #[derive(Clone, Copy, Debug, Format)]
@@ -159,6 +208,12 @@ pub struct DateTime {
     pub time: Time,
     pub date: u8,
     pub day: Day,
+    pub month: u8,
+    /// 2000 to 2199.
+    pub year: u16,
+    /// The oscillator stopped since the time was last set, e.g. because the backup battery is
+    /// flat, so the time is not valid.
+    pub oscillator_stopped: bool,
 }
 
 #[derive(Clone, Copy, Debug, Format)]
@@ -182,3 +237,18 @@ pub enum Day {
     Sunday = 7,
 }
 
+impl Day {
+    /// The day numbered from 1 for Monday to 7 for Sunday.
+    pub fn from_weekday(weekday: u8) -> Self {
+        match weekday {
+            1 => Day::Monday,
+            2 => Day::Tuesday,
+            3 => Day::Wednesday,
+            4 => Day::Thursday,
+            5 => Day::Friday,
+            6 => Day::Saturday,
+            7 => Day::Sunday,
+            _ => Day::None,
+        }
+    }
+}
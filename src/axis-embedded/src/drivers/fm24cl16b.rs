@@ -2,7 +2,7 @@ use core::ops;
 use core::ops::{Add, AddAssign};
 use bitfield::bitfield;
 use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
-use embedded_hal_async::i2c::I2c;
+use embedded_hal_async::i2c::{I2c, Operation};
 
 const ADDR: u8 = 0b1010;
 
@@ -55,6 +55,31 @@ impl<I2C: I2c> Fm24cl16b<I2C> {
         self.read(address, buf).await
     }
 
+    /// Writes data to the FM24CL16B starting at the specified address. FRAM needs no erase or
+    /// write delay, so the data is stored once this returns.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_address`: The address to begin writing at. Writing past the end wraps around to 0.
+    /// * `data`: The bytes to write.
+    ///
+    /// returns: Result<(), Error<<I2C as ErrorType>::Error>>
+    pub async fn write_random(&mut self, new_address: MemoryAddress, data: &[u8]) -> Result<(), Error<I2C::Error>> {
+        if data.len() > ((MemoryAddress::MAX + 1) as usize) {
+            return Err(Error::InvalidBufferSize);
+        }
+        let address = Self::create_address(&new_address);
+        let word = Self::create_word(&new_address);
+
+        // Both writes go out in one transfer, the data directly following the word address
+        self.i2c
+            .transaction(address, &mut [Operation::Write(&[word]), Operation::Write(data)])
+            .await
+            .map_err(Error::I2cError)?;
+        self.memory_address = new_address + data.len() as u16;
+        Ok(())
+    }
+
     async fn read(&mut self, address: u8, buf: &mut [u8]) -> Result<(), Error<I2C::Error>> {
         if buf.len() > ((MemoryAddress::MAX + 1) as usize) {
             return Err(Error::InvalidBufferSize);
@@ -3,4 +3,4 @@ pub mod max31855;
 pub mod tca9534;
 pub mod ds3231m;
 pub mod pca9544a;
-mod fm24cl16b;
\ No newline at end of file
+pub mod fm24cl16b;
\ No newline at end of file
@@ -0,0 +1,34 @@
+use axis_protocol::events::{Event, EventKind, ThermocoupleFault};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::pubsub::{PubSubChannel, Subscriber};
+use embassy_time::Instant;
+
+use crate::dispatcher::Origin;
+use crate::drivers::max31855;
+
+const EVENTS_LEN: usize = 16;
+
+/// Events waiting to be pushed, with one subscriber per connected link so each client gets
+/// every event.
+pub static EVENTS: PubSubChannel<CriticalSectionRawMutex, Event, EVENTS_LEN, { Origin::ALL.len() }, 1> =
+    PubSubChannel::new();
+
+pub type EventSubscriber = Subscriber<'static, CriticalSectionRawMutex, Event, EVENTS_LEN, { Origin::ALL.len() }, 1>;
+
+/// Timestamps `kind` and queues it for every connected client. Events are dropped while no
+/// client is connected, and a client falling behind loses the oldest ones.
+pub fn publish(kind: EventKind) {
+    let event = Event::new(Instant::now().as_micros(), kind);
+    crate::mirror!(info, "Event: {:?}", event);
+    EVENTS.immediate_publisher().publish_immediate(event);
+}
+
+impl From<max31855::FaultInfo> for ThermocoupleFault {
+    fn from(fault: max31855::FaultInfo) -> Self {
+        Self {
+            short_to_vcc: fault.short_to_vcc,
+            short_to_gnd: fault.short_to_gnd,
+            open_circuit: fault.open_circuit,
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use axis_protocol::params;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration};
+
+use crate::params::PARAMS;
+use crate::systems::control;
+
+static HOST_SEEN: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Called for every valid packet from a trusted client, heartbeats included.
+pub fn host_seen() {
+    HOST_SEEN.signal(());
+}
+
+/// Watches for the client going silent for longer than [params::LINK_TIMEOUT], and drops the
+/// machine back to local control when it does.
+#[embassy_executor::task]
+pub async fn supervise_link() {
+    let mut connected = false;
+    loop {
+        let timeout_ms = PARAMS.lock().await.get_u32(params::LINK_TIMEOUT);
+
+        match with_timeout(Duration::from_millis(timeout_ms as u64), HOST_SEEN.wait()).await {
+            Ok(()) => {
+                if !connected {
+                    crate::forward!(info, "Host link established");
+                    connected = true;
+                }
+            }
+            Err(_) => {
+                if connected {
+                    crate::forward!(warn, "Host link lost, no packet for {} ms", timeout_ms);
+                    connected = false;
+                    control::enter_failsafe().await;
+                }
+            }
+        }
+    }
+}
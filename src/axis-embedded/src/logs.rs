@@ -0,0 +1,129 @@
+use core::cell::RefCell;
+use core::fmt;
+
+use axis_protocol::logs::{LogLevel, LogRecord, RateLimiter};
+use embassy_rp::peripherals::USB;
+use embassy_rp::usb::Driver;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::pubsub::{PubSubChannel, Subscriber};
+use embassy_time::Instant;
+use embassy_usb::class::cdc_acm::CdcAcmClass;
+
+use crate::dispatcher::Origin;
+
+const LOGS_LEN: usize = 8;
+const LINKS: usize = Origin::ALL.len();
+
+/// Log records waiting to be pushed, with one subscriber per connected link. A link falling
+/// behind loses the oldest records, and adds them to the `dropped` count of the next one.
+pub static LOGS: PubSubChannel<CriticalSectionRawMutex, LogRecord, LOGS_LEN, LINKS, 1> = PubSubChannel::new();
+
+pub type LogSubscriber = Subscriber<'static, CriticalSectionRawMutex, LogRecord, LOGS_LEN, LINKS, 1>;
+
+/// Bytes of log output buffered for the USB log port.
+const USB_LOG_BUFFER: usize = 1024;
+
+/// Writes `log` output to the USB log port, for reading with any serial terminal while the
+/// client owns the protocol port.
+#[embassy_executor::task]
+pub async fn log_usb(class: CdcAcmClass<'static, Driver<'static, USB>>) {
+    embassy_usb_logger::with_class!(USB_LOG_BUFFER, log::LevelFilter::Info, class).await
+}
+
+/// Records forwarded in a burst, and on average per second, so logging cannot starve telemetry.
+const BURST: u32 = 8;
+const PER_SECOND: u32 = 20;
+
+static SINK: Mutex<CriticalSectionRawMutex, RefCell<Sink>> = Mutex::new(RefCell::new(Sink::new()));
+
+/// Level a link starts with.
+const DEFAULT_LEVEL: Option<LogLevel> = Some(LogLevel::Info);
+
+struct Sink {
+    /// Least severe level forwarded to each link, by [Origin::index].
+    levels: [Option<LogLevel>; LINKS],
+    limiter: RateLimiter,
+}
+
+impl Sink {
+    const fn new() -> Self {
+        Self {
+            levels: [DEFAULT_LEVEL; LINKS],
+            limiter: RateLimiter::new(BURST, PER_SECOND),
+        }
+    }
+}
+
+/// Sets the least severe level forwarded to the client on `origin`, or stops forwarding to it
+/// with `None`.
+pub fn set_level(origin: Origin, level: Option<LogLevel>) {
+    SINK.lock(|sink| sink.borrow_mut().levels[origin.index()] = level);
+}
+
+/// Goes back to the default level for `origin`, e.g. when its client disconnects.
+pub fn reset_level(origin: Origin) {
+    set_level(origin, DEFAULT_LEVEL);
+}
+
+/// Whether records of `level` are forwarded to the client on `origin`.
+pub fn enabled(origin: Origin, level: LogLevel) -> bool {
+    SINK.lock(|sink| sink.borrow().levels[origin.index()].is_some_and(|min| level >= min))
+}
+
+/// Queues a record for the clients if its level is enabled on any link and the rate limit
+/// allows it, links with a less verbose level skip it, see [enabled]. Use the [forward!] macro
+/// rather than calling this directly.
+pub fn forward(level: LogLevel, module: &str, args: fmt::Arguments) {
+    SINK.lock(|sink| {
+        let mut sink = sink.borrow_mut();
+        if !sink.levels.iter().flatten().any(|&min| level >= min) {
+            return;
+        }
+
+        let now = Instant::now();
+        if !sink.limiter.allow(now.as_millis()) {
+            return;
+        }
+
+        let mut record = LogRecord::new(now.as_micros(), level, module, args);
+        record.dropped = sink.limiter.take_dropped();
+        LOGS.immediate_publisher().publish_immediate(record);
+    });
+}
+
+/// Logs through defmt and the USB log port, without forwarding to the client. For messages about
+/// the client link itself, which forwarding would feed back into, and for noise it does not need.
+///
+/// ```ignore
+/// mirror!(debug, "Outbound: {:?}", outbound);
+/// ```
+#[macro_export]
+macro_rules! mirror {
+    ($level:ident, $($arg:tt)*) => {{
+        defmt::$level!($($arg)*);
+        log::$level!($($arg)*);
+    }};
+}
+
+/// Logs through defmt and the USB log port, and forwards the same message to the client.
+///
+/// ```ignore
+/// forward!(warn, "Link lost after {} ms", timeout);
+/// ```
+#[macro_export]
+macro_rules! forward {
+    (trace, $($arg:tt)*) => { $crate::forward!(@ trace, Trace, $($arg)*) };
+    (debug, $($arg:tt)*) => { $crate::forward!(@ debug, Debug, $($arg)*) };
+    (info, $($arg:tt)*) => { $crate::forward!(@ info, Info, $($arg)*) };
+    (warn, $($arg:tt)*) => { $crate::forward!(@ warn, Warn, $($arg)*) };
+    (error, $($arg:tt)*) => { $crate::forward!(@ error, Error, $($arg)*) };
+    (@ $defmt:ident, $level:ident, $($arg:tt)*) => {{
+        $crate::mirror!($defmt, $($arg)*);
+        $crate::logs::forward(
+            axis_protocol::logs::LogLevel::$level,
+            module_path!(),
+            format_args!($($arg)*),
+        );
+    }};
+}
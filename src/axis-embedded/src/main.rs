@@ -6,7 +6,19 @@
 
 extern crate alloc;
 
+mod auth;
+mod clock;
 mod client_communicator;
+mod device;
+mod dispatcher;
+mod events;
+mod link;
+mod logs;
+mod params;
+mod pid;
+mod telemetry;
+mod transport;
+mod net;
 mod systems;
 mod drivers;
 
@@ -16,7 +28,7 @@ use core::future::Future;
 use core::ops::Deref;
 use assign_resources::assign_resources;
 use cortex_m::prelude::_embedded_hal_blocking_i2c_Write;
-use defmt::{error, info, unwrap};
+use defmt::unwrap;
 use defmt::Format;
 use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
 use embassy_embedded_hal::shared_bus::asynch::spi::SpiDevice;
@@ -36,6 +48,9 @@ use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::mutex::Mutex;
 use crate::drivers::ads1119;
 use crate::drivers::pca9544a::Channel;
+use axis_protocol::events::{self as protocol_events, EventKind, ThermocoupleFault};
+use axis_protocol::handshake::SerialNumber;
+use axis_protocol::units::{Bar, Celsius};
 
 use cortex_m_rt::entry;
 use embedded_alloc::LlffHeap as Heap;
@@ -43,7 +58,6 @@ use embedded_alloc::LlffHeap as Heap;
 use alloc::{format, vec, vec::Vec};
 use deku::prelude::*;
 
-pub const MAX_PACKET_SIZE: usize = 64;
 pub const THERMOCOUPLE_SPI_FREQUENCY: u32 = 500_000;
 
 pub type SpiBus<'a, T> = Mutex<CriticalSectionRawMutex, Spi<'a, T, spi::Async>>;
@@ -155,11 +169,28 @@ assign_resources! {
         zc_sig: PIN_21,
         hv_io2: PIN_28
     }
-    other: OtherResources {
-        led: PIN_25,
+    wifi: WifiResources {
+        pwr: PIN_23,
+        cs: PIN_25,
+        dio: PIN_24,
+        clk: PIN_29,
+        pio: PIO0,
+        dma: DMA_CH4,
+    }
+    usb: UsbResources {
+        usb: USB,
+    }
+    flash: FlashResources {
+        flash: FLASH,
     }
 }
 
+/// Packets queued for the client connected over USB.
+const USB_OUTBOUND_LEN: usize = 8;
+
+type UsbCommunicator = client_communicator::UsbWrapperInner<'static, USB_OUTBOUND_LEN>;
+type UsbDriver = embassy_rp::usb::Driver<'static, peripherals::USB>;
+
 #[cortex_m_rt::entry]
 fn main() -> ! {
     // Initialize the allocator BEFORE you use it
@@ -173,6 +204,9 @@ fn main() -> ! {
     let p = embassy_rp::init(Default::default());
     let r = split_resources!(p);
 
+    // Before anything else runs, reading the ID stalls execution from flash
+    let serial = device::read_serial_number(r.flash.flash);
+
     let i2c0 = I2c::new_async(r.i2c0.peripheral, r.i2c0.scl, r.i2c0.sda, I2c0Irqs, Config::default());
     let i2c1 = I2c::new_async(r.i2c1.peripheral, r.i2c1.scl, r.i2c1.sda, I2c1Irqs, Config::default());
 
@@ -209,10 +243,32 @@ fn main() -> ! {
     let spawner = EXECUTOR_MED.start(interrupt::SWI_IRQ_0);
     unwrap!(spawner.spawn(read_ads(i2c1_bus)));
 
+    let usb_data = client_communicator::UsbData {
+        device_descriptor: make_static!([0u8; 256]),
+        config_descriptor: make_static!([0u8; 256]),
+        bos_descriptor: make_static!([0u8; 256]),
+        control_buf: make_static!([0u8; 64]),
+        state: make_static!(embassy_usb::class::cdc_acm::State::new()),
+        log_state: make_static!(embassy_usb::class::cdc_acm::State::new()),
+        serial_number: serial.hex(make_static!([0u8; SerialNumber::HEX_LEN])),
+    };
+    let (communicator, usb, log_class) = UsbCommunicator::new(r.usb.usb, usb_data);
+
     // Low priority executor: runs in thread mode, using WFE/SEV
     let executor = EXECUTOR_LOW.init(Executor::new());
     executor.run(|spawner| {
-        unwrap!(spawner.spawn(blink(r.other)));
+        unwrap!(spawner.spawn(run_usb(usb)));
+        unwrap!(spawner.spawn(communicate_usb(make_static!(communicator))));
+        unwrap!(spawner.spawn(logs::log_usb(log_class)));
+        if net::radio_available() {
+            unwrap!(spawner.spawn(net::run_network(spawner, r.wifi)));
+        } else {
+            // Without the radio, GPIO 25 drives the LED rather than the radio's chip select
+            unwrap!(spawner.spawn(blink(r.wifi.cs)));
+        }
+        unwrap!(spawner.spawn(telemetry::publish_telemetry()));
+        unwrap!(spawner.spawn(link::supervise_link()));
+        unwrap!(spawner.spawn(persist_params(i2c0_bus)));
     });
 }
 
@@ -221,25 +277,50 @@ async fn read_max31855(cs: Output<'static>, spi_bus: &'static Spi0Bus) {
     let device = SpiDevice::new(spi_bus, cs);
 
     let mut max = drivers::max31855::Max31855::new(device);
+    let mut boiler = systems::boiler::Boiler::new();
+    let mut last_fault = None;
 
     loop {
-        let res = max.read_raw().await;
+        let res = max.read_thcpl_temp().await;
         match res {
             Ok(value) => {
-                //info!("Success");
-                //info!("Temp: {:?}", value.get_temp());
+                last_fault = None;
+                telemetry::record(|r| r.boiler = Some(Celsius(value)));
+                systems::control::check_temperature(Celsius(value)).await;
+                boiler.update(Celsius(value)).await;
             },
+            Err(drivers::max31855::Error::FaultDetected(fault)) => {
+                telemetry::record(|r| r.boiler = None);
+                boiler.fault().await;
+                let fault = ThermocoupleFault::from(fault);
+                if last_fault != Some(fault) {
+                    events::publish(EventKind::ThermocoupleFault(fault));
+                }
+                last_fault = Some(fault);
+            }
             Err(e) => {
-                error!("Error or something :(")
+                boiler.fault().await;
+                crate::mirror!(error, "Error or something :(")
             }
         }
-        Timer::after_millis(250).await;
+        // The MAX31855 completes a conversion roughly every 100 ms
+        Timer::after_millis(100).await;
     }
 }
 
 #[embassy_executor::task]
-async fn blink(other: OtherResources) {
-    let mut led = Output::new(other.led, Level::Low);
+async fn run_usb(mut usb: embassy_usb::UsbDevice<'static, UsbDriver>) -> ! {
+    usb.run().await
+}
+
+#[embassy_executor::task]
+async fn communicate_usb(communicator: &'static mut UsbCommunicator) -> ! {
+    communicator.run().await
+}
+
+#[embassy_executor::task]
+async fn blink(led: peripherals::PIN_25) {
+    let mut led = Output::new(led, Level::Low);
 
     loop {
         //info!("led on");
@@ -252,6 +333,12 @@ async fn blink(other: OtherResources) {
     }
 }
 
+#[embassy_executor::task]
+async fn persist_params(bus: &'static I2c0Bus) -> ! {
+    let mut fram = drivers::fm24cl16b::Fm24cl16b::new(I2cDevice::new(bus));
+    params::persist(&mut fram).await
+}
+
 #[embassy_executor::task]
 async fn read_ads(bus: &'static I2c1Bus) {
     let mut pca_i2c_device = I2cDevice::new(bus);
@@ -268,14 +355,63 @@ async fn read_ads(bus: &'static I2c1Bus) {
     config.set_mux(ads1119::MuxConfig::AIN0_AGND);
     config.set_vref(ads1119::VoltageReference::External);
     config.set_conversion_mode(ads1119::ConversionMode::Continuous);
-    ads1119.configure(config).await.unwrap();
-    ads1119.start_conversion().await.unwrap();
+    if ads1119.configure(config).await.is_err() || ads1119.start_conversion().await.is_err() {
+        events::publish(EventKind::I2cFailure { device: protocol_events::I2cDevice::Ads1119 });
+    }
 
+    // Failures are only reported when a device stops responding, not on every read
+    let mut ads_ok = true;
+    let mut rtc_ok = true;
+    let mut ticks: u32 = 0;
     loop {
-        Timer::after_secs(1).await;
+        Timer::after_millis(100).await;
+        ticks = ticks.wrapping_add(1);
 
-        let value = ads1119.read_data().await.unwrap();
+        match ads1119.read_data().await {
+            Ok(value) => {
+                ads_ok = true;
+                telemetry::record(|r| r.pressure = Some(pressure(value)));
+            }
+            Err(_) => {
+                telemetry::record(|r| r.pressure = None);
+                if core::mem::replace(&mut ads_ok, false) {
+                    events::publish(EventKind::I2cFailure { device: protocol_events::I2cDevice::Ads1119 });
+                }
+            }
+        }
+
+        if let Some(date_time) = clock::take_pending() {
+            if ds3213.write_date_time(&date_time).await.is_err() {
+                clock::record_failure();
+            }
+        }
 
-        let a = ds3213.read_date_time().await.unwrap();
+        // The RTC only changes once a second
+        if ticks % 10 != 0 {
+            continue;
+        }
+
+        match ds3213.read_date_time().await {
+            Ok(a) => {
+                rtc_ok = true;
+                clock::record(&a);
+                telemetry::record(|r| r.rtc_time = Some((a.time.hour, a.time.minute, a.time.second)));
+            }
+            Err(_) => {
+                clock::record_failure();
+                if core::mem::replace(&mut rtc_ok, false) {
+                    events::publish(EventKind::I2cFailure { device: protocol_events::I2cDevice::Ds3231m });
+                }
+            }
+        }
     }
 }
+
+/// Converts a single ended ADS1119 reading of a ratiometric 0.5-4.5 V transducer into bar.
+fn pressure(raw: i16) -> Bar {
+    const FULL_SCALE: f32 = i16::MAX as f32;
+    const SENSOR_MAX_BAR: f32 = 12.0;
+
+    let ratio = raw as f32 / FULL_SCALE;
+    Bar(((ratio - 0.1) / 0.8 * SENSOR_MAX_BAR).max(0.0))
+}
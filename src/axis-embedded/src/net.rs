@@ -0,0 +1,145 @@
+use axis_protocol::transport::{FramedStream, DEFAULT_TCP_PORT};
+use cyw43_pio::PioSpi;
+use embassy_executor::Spawner;
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{Config, Stack, StackResources};
+use embassy_rp::bind_interrupts;
+use embassy_rp::clocks::RoscRng;
+use embassy_rp::gpio::{Level, Output};
+use embassy_rp::peripherals::{DMA_CH4, PIO0};
+use embassy_rp::pio::{InterruptHandler, Pio};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Timer};
+use rand::RngCore;
+use static_cell::StaticCell;
+
+use crate::client_communicator::{self, Outbound, MAX_FRAME_SIZE};
+use crate::dispatcher::Origin;
+use crate::WifiResources;
+
+/// Packets queued for the client connected over TCP.
+pub static OUTBOUND: Channel<CriticalSectionRawMutex, Outbound, 8> = Channel::new();
+
+/// Network to join, set at build time. The TCP transport stays off when it is not.
+const WIFI_SSID: Option<&str> = option_env!("AXIS_WIFI_SSID");
+const WIFI_PASSWORD: Option<&str> = option_env!("AXIS_WIFI_PASSWORD");
+
+/// The radio firmware is not linked into the image, flash it once next to it with
+/// `probe-rs download 43439A0.bin --binary-format bin --chip RP2040 --base-address 0x10100000`
+/// and `43439A0_clm.bin` at `0x10140000`. memory.x keeps the image to the first megabyte of
+/// flash, below them.
+const FIRMWARE_ADDRESS: usize = 0x1010_0000;
+const FIRMWARE_LEN: usize = 230321;
+const CLM_ADDRESS: usize = 0x1014_0000;
+const CLM_LEN: usize = 4752;
+
+bind_interrupts!(struct PioIrqs {
+    PIO0_IRQ_0 => InterruptHandler<PIO0>;
+});
+
+type WifiSpi = PioSpi<'static, PIO0, 0, DMA_CH4>;
+type NetStack = Stack<cyw43::NetDriver<'static>>;
+
+/// Whether a network is configured and the radio firmware is flashed, so [run_network] can
+/// bring the radio up. Anything else sent to the radio would be garbage.
+pub fn radio_available() -> bool {
+    let (Some(ssid), Some(_)) = (WIFI_SSID, WIFI_PASSWORD) else {
+        crate::forward!(info, "No network configured, the TCP transport is disabled");
+        return false;
+    };
+    if !is_flashed(FIRMWARE_ADDRESS) || !is_flashed(CLM_ADDRESS) {
+        crate::forward!(warn, "Network {} configured, but the radio firmware is not flashed", ssid);
+        return false;
+    }
+    true
+}
+
+/// Whether anything was written at `address`, which reads as all ones while erased.
+fn is_flashed(address: usize) -> bool {
+    // Safety: the address is inside the flash mapped by XIP
+    let start = unsafe { core::slice::from_raw_parts(address as *const u8, 16) };
+    start.iter().any(|&byte| byte != 0xff)
+}
+
+/// Brings up the radio, blinks the LED wired to it and, once the configured network is joined,
+/// serves clients over TCP. Only to be spawned if [radio_available].
+#[embassy_executor::task]
+pub async fn run_network(spawner: Spawner, r: WifiResources) -> ! {
+    let pwr = Output::new(r.pwr, Level::Low);
+    let cs = Output::new(r.cs, Level::High);
+    let mut pio = Pio::new(r.pio, PioIrqs);
+    let spi = PioSpi::new(&mut pio.common, pio.sm0, pio.irq0, cs, r.dio, r.clk, r.dma);
+
+    // Safety: the blobs are flashed at these addresses, and nothing writes to flash there
+    let firmware = unsafe { core::slice::from_raw_parts(FIRMWARE_ADDRESS as *const u8, FIRMWARE_LEN) };
+    let clm = unsafe { core::slice::from_raw_parts(CLM_ADDRESS as *const u8, CLM_LEN) };
+
+    static STATE: StaticCell<cyw43::State> = StaticCell::new();
+    let (device, mut control, runner) = cyw43::new(STATE.init(cyw43::State::new()), pwr, spi, firmware).await;
+    defmt::unwrap!(spawner.spawn(run_radio(runner)));
+
+    control.init(clm).await;
+    control.set_power_management(cyw43::PowerManagementMode::PowerSave).await;
+
+    static RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+    static STACK: StaticCell<NetStack> = StaticCell::new();
+    let config = Config::dhcpv4(Default::default());
+    let stack = &*STACK.init(Stack::new(device, config, RESOURCES.init(StackResources::new()), RoscRng.next_u64()));
+    defmt::unwrap!(spawner.spawn(run_stack(stack)));
+
+    if let (Some(ssid), Some(password)) = (WIFI_SSID, WIFI_PASSWORD) {
+        while let Err(e) = control.join_wpa2(ssid, password).await {
+            crate::forward!(warn, "Failed to join {}, status {}, retrying", ssid, e.status);
+            Timer::after_secs(5).await;
+        }
+        stack.wait_config_up().await;
+        crate::forward!(info, "Joined {}, serving TCP on port {}", ssid, DEFAULT_TCP_PORT);
+        defmt::unwrap!(spawner.spawn(serve_tcp(stack)));
+    }
+
+    // The LED is wired to the radio, as GPIO 0 of the CYW43
+    loop {
+        control.gpio_set(0, true).await;
+        Timer::after_secs(1).await;
+
+        control.gpio_set(0, false).await;
+        Timer::after_secs(1).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn run_radio(runner: cyw43::Runner<'static, Output<'static>, WifiSpi>) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn run_stack(stack: &'static NetStack) -> ! {
+    stack.run().await
+}
+
+/// Serves one client at a time over TCP, once the radio has joined a network.
+#[embassy_executor::task]
+async fn serve_tcp(stack: &'static NetStack) -> ! {
+    let mut rx_buffer = [0u8; 1024];
+    let mut tx_buffer = [0u8; 1024];
+    loop {
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+        // Heartbeats keep a healthy connection busy
+        socket.set_timeout(Some(Duration::from_secs(5)));
+
+        if let Err(e) = socket.accept(DEFAULT_TCP_PORT).await {
+            crate::forward!(warn, "Failed to accept TCP connection: {:?}", e);
+            continue;
+        }
+        crate::forward!(info, "Client connected over TCP from {:?}", socket.remote_endpoint());
+
+        let mut transport = FramedStream::<_, MAX_FRAME_SIZE>::new(socket);
+        let error = client_communicator::serve(&mut transport, &OUTBOUND.receiver(), Origin::Network).await;
+        crate::forward!(info, "TCP connection closed: {:?}", error);
+
+        let mut socket = transport.into_inner();
+        socket.close();
+        let _ = socket.flush().await;
+    }
+}
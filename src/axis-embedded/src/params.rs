@@ -0,0 +1,137 @@
+use axis_protocol::events::{EventKind, I2cDevice};
+use axis_protocol::params::{
+    self, ParamError, ParamId, ParamValue, StoredValues, DESCRIPTORS, MAX_PARAMS, MAX_STORED_LEN,
+};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embedded_hal_async::i2c::I2c;
+
+use crate::drivers::fm24cl16b::{Fm24cl16b, MemoryAddress};
+use crate::events;
+use crate::pid::Pid;
+
+const PARAM_COUNT: usize = DESCRIPTORS.len();
+
+/// Where the persisted values start in the FRAM, followed by up to [MAX_STORED_LEN] bytes.
+const STORAGE_ADDRESS: u16 = 0;
+
+/// Live values of every parameter in [DESCRIPTORS].
+pub static PARAMS: Mutex<CriticalSectionRawMutex, Registry> = Mutex::new(Registry::new());
+
+/// Raised when a persisted parameter changed, so [persist] writes it out.
+static CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+pub struct Registry {
+    values: [ParamValue; PARAM_COUNT],
+}
+
+impl Registry {
+    pub const fn new() -> Self {
+        let mut values = [ParamValue::Bool(false); PARAM_COUNT];
+        let mut i = 0;
+        while i < PARAM_COUNT {
+            values[i] = DESCRIPTORS[i].default;
+            i += 1;
+        }
+        Self { values }
+    }
+
+    pub fn get(&self, id: ParamId) -> Result<ParamValue, ParamError> {
+        Self::index(id).map(|i| self.values[i])
+    }
+
+    /// Writes `value` if it is valid for `id`, returning the stored value.
+    pub fn set(&mut self, id: ParamId, value: ParamValue) -> Result<ParamValue, ParamError> {
+        let i = Self::index(id)?;
+        let value = DESCRIPTORS[i].validate(value)?;
+        if DESCRIPTORS[i].persist && self.values[i] != value {
+            CHANGED.signal(());
+        }
+        self.values[i] = value;
+        Ok(value)
+    }
+
+    /// Values of the parameters flagged `persist`.
+    pub fn persisted(&self) -> StoredValues {
+        DESCRIPTORS
+            .iter()
+            .zip(self.values)
+            .filter(|(d, _)| d.persist)
+            .map(|(d, value)| (d.id, value))
+            .take(MAX_PARAMS)
+            .collect()
+    }
+
+    /// Takes back values returned by [persisted](Self::persisted) before a reset. Values of
+    /// parameters that are unknown, no longer persisted or no longer valid are skipped.
+    pub fn restore(&mut self, values: &StoredValues) {
+        for &(id, value) in values {
+            let Ok(i) = Self::index(id) else {
+                continue;
+            };
+            if let Ok(value) = DESCRIPTORS[i].validate(value) {
+                if DESCRIPTORS[i].persist {
+                    self.values[i] = value;
+                }
+            }
+        }
+    }
+
+    pub fn get_f32(&self, id: ParamId) -> f32 {
+        match self.get(id) {
+            Ok(ParamValue::F32(v)) => v,
+            _ => 0.0,
+        }
+    }
+
+    pub fn get_u32(&self, id: ParamId) -> u32 {
+        match self.get(id) {
+            Ok(ParamValue::U32(v)) => v,
+            _ => 0,
+        }
+    }
+
+    /// Copies the boiler parameters into `pid`.
+    pub fn apply_boiler_pid(&self, pid: &mut Pid<f32>) {
+        pid.setpoint(self.get_f32(params::BOILER_SETPOINT))
+            .p(self.get_f32(params::BOILER_KP), self.get_f32(params::BOILER_P_LIMIT))
+            .i(self.get_f32(params::BOILER_KI), self.get_f32(params::BOILER_I_LIMIT))
+            .d(self.get_f32(params::BOILER_KD), self.get_f32(params::BOILER_D_LIMIT));
+        pid.output_limit = self.get_f32(params::BOILER_OUTPUT_LIMIT);
+    }
+
+    fn index(id: ParamId) -> Result<usize, ParamError> {
+        DESCRIPTORS
+            .iter()
+            .position(|d| d.id == id)
+            .ok_or(ParamError::UnknownParameter)
+    }
+}
+
+/// Restores the persisted parameters from the FRAM, then writes them back whenever one changes.
+/// FRAM takes practically unlimited writes, so every change is written right away.
+pub async fn persist<I: I2c>(fram: &mut Fm24cl16b<I>) -> ! {
+    let address = MemoryAddress::new(STORAGE_ADDRESS).unwrap();
+    let mut buf = [0u8; MAX_STORED_LEN];
+
+    match fram.read_random(address, &mut buf).await {
+        Ok(()) => match params::load(&buf) {
+            Some(values) => PARAMS.lock().await.restore(&values),
+            None => crate::mirror!(info, "No stored parameters, starting from the defaults"),
+        },
+        Err(_) => events::publish(EventKind::I2cFailure { device: I2cDevice::Fm24cl16b }),
+    }
+
+    loop {
+        CHANGED.wait().await;
+        let values = PARAMS.lock().await.persisted();
+        // The buffer holds every parameter there can be
+        let Ok(stored) = params::store(&values, &mut buf) else {
+            continue;
+        };
+        if fram.write_random(address, stored).await.is_err() {
+            events::publish(EventKind::I2cFailure { device: I2cDevice::Fm24cl16b });
+        }
+    }
+}
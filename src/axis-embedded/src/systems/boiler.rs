@@ -0,0 +1,64 @@
+//! Boiler temperature control: a PID loop run on every thermocouple reading, tuned by the
+//! `boiler.*` parameters.
+
+use axis_protocol::events::Mode;
+use axis_protocol::units::Celsius;
+use embassy_time::Instant;
+
+use crate::params::PARAMS;
+use crate::pid::Pid;
+use crate::systems::control::{self, CONTROL};
+use crate::telemetry;
+
+/// Heater output range, in %.
+const MIN_DUTY: f32 = 0.0;
+const MAX_DUTY: f32 = 100.0;
+
+pub struct Boiler {
+    pid: Pid<f32>,
+    last_reading: Option<Instant>,
+}
+
+impl Boiler {
+    pub fn new() -> Self {
+        Self {
+            pid: Pid::new(0.0, 0.0),
+            last_reading: None,
+        }
+    }
+
+    /// Sets the heater output for a boiler reading, using the gains and limits currently stored
+    /// and the setpoint in use. The heater stays off in [Mode::Standby] and [Mode::Fault].
+    pub async fn update(&mut self, temperature: Celsius) {
+        let now = Instant::now();
+        // The first reading has nothing to integrate or derive from
+        let delta_ms = self
+            .last_reading
+            .replace(now)
+            .map_or(0.0, |last| (now - last).as_millis() as f32);
+
+        PARAMS.lock().await.apply_boiler_pid(&mut self.pid);
+        self.pid.setpoint(control::setpoint().await.0);
+        let output = self.pid.next_control_output(temperature.0, delta_ms);
+
+        let mut control = CONTROL.lock().await;
+        let duty = if matches!(control.mode, Mode::Standby | Mode::Fault) {
+            self.pid.reset_integral_term();
+            MIN_DUTY
+        } else {
+            output.output.clamp(MIN_DUTY, MAX_DUTY)
+        };
+        control.heater_duty = duty;
+        drop(control);
+
+        telemetry::record(|r| r.heater_duty = Some(duty));
+    }
+
+    /// Turns the heater off while the boiler temperature cannot be read.
+    pub async fn fault(&mut self) {
+        self.last_reading = None;
+        self.pid.reset_integral_term();
+        CONTROL.lock().await.heater_duty = MIN_DUTY;
+        telemetry::record(|r| r.heater_duty = Some(MIN_DUTY));
+    }
+}
@@ -0,0 +1,158 @@
+use axis_protocol::events::{EventKind, Mode};
+use axis_protocol::messages::CommandError;
+use axis_protocol::params::{self, ParamValue};
+use axis_protocol::units::Celsius;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::Instant;
+
+use crate::events;
+use crate::params::PARAMS;
+
+/// Boiler temperature that trips the machine into [Mode::Fault], above the highest setpoint
+/// [params::BOILER_SETPOINT] accepts.
+pub const OVER_TEMPERATURE: Celsius = Celsius(165.0);
+
+/// What the heater and pump are currently told to do, by the local controls or the client.
+pub static CONTROL: Mutex<CriticalSectionRawMutex, ControlState> = Mutex::new(ControlState::new());
+
+pub struct ControlState {
+    pub mode: Mode,
+    /// Boiler setpoint in use. Follows the stored setpoint unless the client overrides it.
+    pub setpoint: Option<Celsius>,
+    /// Pump output, 0 to 100 %.
+    pub pump_duty: f32,
+    /// Heater output, 0 to 100 %, set by the [boiler](crate::systems::boiler) loop.
+    pub heater_duty: f32,
+    /// Whether the client is driving the machine.
+    pub remote: bool,
+    /// When the running shot started.
+    shot_started: Option<Instant>,
+}
+
+impl ControlState {
+    pub const fn new() -> Self {
+        Self {
+            mode: Mode::Standby,
+            setpoint: None,
+            pump_duty: 0.0,
+            heater_duty: 0.0,
+            remote: false,
+            shot_started: None,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: Mode) {
+        if self.mode != mode {
+            events::publish(EventKind::ModeChanged { from: self.mode, to: mode });
+            self.mode = mode;
+        }
+    }
+
+    fn start_shot(&mut self) {
+        self.shot_started = Some(Instant::now());
+        events::publish(EventKind::ShotStarted);
+    }
+
+    /// Stops the pump, ending the running shot if there is one.
+    fn stop_pump(&mut self) {
+        self.pump_duty = 0.0;
+        if let Some(started) = self.shot_started.take() {
+            let duration_ms = started.elapsed().as_millis() as u32;
+            events::publish(EventKind::ShotStopped { duration_ms });
+        }
+    }
+}
+
+/// Returns the machine to local control after the client went away: stops the pump and drops
+/// any setpoint override so the stored setpoint applies again. Only a mode the client drove the
+/// machine into is left, a latched fault or standby stays as it is.
+pub async fn enter_failsafe() {
+    let stored_setpoint = PARAMS.lock().await.get_f32(params::BOILER_SETPOINT);
+
+    let mut control = CONTROL.lock().await;
+    crate::forward!(warn, "Entering failsafe, returning to {} °C", stored_setpoint);
+
+    control.stop_pump();
+    control.setpoint = None;
+    let remote = core::mem::replace(&mut control.remote, false);
+    let mode = failsafe_mode(control.mode, remote);
+    control.set_mode(mode);
+}
+
+/// The mode to fall back to from `mode` when the client goes away. Every mode is listed, so a
+/// new one has to be classified before it builds.
+const fn failsafe_mode(mode: Mode, remote: bool) -> Mode {
+    match mode {
+        // Only the client could have started these
+        Mode::Brewing | Mode::Steaming if remote => Mode::Heating,
+        Mode::Brewing | Mode::Steaming => mode,
+        // Never leave a fault or wake a machine in standby without someone at the controls
+        Mode::Fault | Mode::Standby => mode,
+        Mode::Heating | Mode::Ready => mode,
+    }
+}
+
+/// Checks a boiler reading against [OVER_TEMPERATURE], latching [Mode::Fault] with the pump
+/// stopped when it is exceeded. Called by the task reading the thermocouple.
+pub async fn check_temperature(temperature: Celsius) {
+    if temperature.0 <= OVER_TEMPERATURE.0 {
+        return;
+    }
+
+    let mut control = CONTROL.lock().await;
+    if control.mode == Mode::Fault {
+        return;
+    }
+    crate::forward!(error, "Boiler at {} °C, over the {} °C limit", temperature.0, OVER_TEMPERATURE.0);
+    events::publish(EventKind::OverTemperature {
+        temperature,
+        limit: OVER_TEMPERATURE,
+    });
+    control.stop_pump();
+    control.heater_duty = 0.0;
+    control.set_mode(Mode::Fault);
+}
+
+/// Boiler setpoint in use, the client's override or else the stored one.
+pub async fn setpoint() -> Celsius {
+    if let Some(setpoint) = CONTROL.lock().await.setpoint {
+        return setpoint;
+    }
+    Celsius(PARAMS.lock().await.get_f32(params::BOILER_SETPOINT))
+}
+
+/// Overrides the stored setpoint until the client goes away. Takes the same range as
+/// [params::BOILER_SETPOINT].
+pub async fn set_setpoint(setpoint: Celsius) -> Result<(), CommandError> {
+    let descriptor = params::descriptor(params::BOILER_SETPOINT).ok_or(CommandError::Unsupported)?;
+    descriptor.validate(ParamValue::F32(setpoint.0)).map_err(CommandError::Param)?;
+
+    let mut control = CONTROL.lock().await;
+    control.setpoint = Some(setpoint);
+    control.remote = true;
+    Ok(())
+}
+
+/// Starts pulling a shot at full pump power, unless one is already running or the machine is
+/// faulted.
+pub async fn start_shot() -> Result<(), CommandError> {
+    let mut control = CONTROL.lock().await;
+    if matches!(control.mode, Mode::Brewing | Mode::Fault) {
+        return Err(CommandError::Busy);
+    }
+
+    control.remote = true;
+    control.pump_duty = 100.0;
+    control.start_shot();
+    control.set_mode(Mode::Brewing);
+    Ok(())
+}
+
+pub async fn stop_shot() {
+    let mut control = CONTROL.lock().await;
+    control.stop_pump();
+    if control.mode == Mode::Brewing {
+        control.set_mode(Mode::Heating);
+    }
+}
@@ -0,0 +1,2 @@
+pub mod boiler;
+pub mod control;
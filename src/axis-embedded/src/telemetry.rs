@@ -0,0 +1,181 @@
+use core::cell::{Cell, RefCell};
+
+use axis_protocol::messages::Messages;
+use axis_protocol::delta::DeltaEncoder;
+use axis_protocol::telemetry::{Channels, Scheduler, SubscribeError, TelemetryEncoding, TelemetryFrame, Topic};
+use axis_protocol::units::{Bar, Celsius, MillilitersPerSecond};
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Instant, Timer};
+
+use crate::dispatcher::Origin;
+
+const LINKS: usize = Origin::ALL.len();
+
+/// Telemetry waiting to be sent, one queue per link, indexed by [Origin::index].
+static TELEMETRY: [Channel<CriticalSectionRawMutex, Messages, 8>; LINKS] = [Channel::new(), Channel::new()];
+
+/// Each link subscribes to its own topics, so clients on USB and TCP do not change each other's
+/// rates.
+static SCHEDULERS: Mutex<CriticalSectionRawMutex, RefCell<[Scheduler; LINKS]>> =
+    Mutex::new(RefCell::new([Scheduler::new(), Scheduler::new()]));
+static SUBSCRIPTIONS_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+static ENCODINGS: Mutex<CriticalSectionRawMutex, Cell<[TelemetryEncoding; LINKS]>> =
+    Mutex::new(Cell::new([TelemetryEncoding::Full; LINKS]));
+
+static LATEST: Mutex<CriticalSectionRawMutex, Cell<Readings>> = Mutex::new(Cell::new(Readings::new()));
+
+/// Most recent value of every sensor, `None` until the first successful read.
+#[derive(Clone, Copy)]
+pub struct Readings {
+    pub boiler: Option<Celsius>,
+    pub pressure: Option<Bar>,
+    pub flow: Option<MillilitersPerSecond>,
+    pub pump_duty: Option<f32>,
+    pub heater_duty: Option<f32>,
+    pub rtc_time: Option<(u8, u8, u8)>,
+}
+
+impl Readings {
+    pub const fn new() -> Self {
+        Self {
+            boiler: None,
+            pressure: None,
+            flow: None,
+            pump_duty: None,
+            heater_duty: None,
+            rtc_time: None,
+        }
+    }
+
+    fn channels(&self) -> Channels {
+        Channels {
+            boiler: self.boiler.unwrap_or(Celsius(f32::NAN)),
+            pressure: self.pressure.unwrap_or(Bar(f32::NAN)),
+            flow: self.flow.unwrap_or(MillilitersPerSecond(f32::NAN)),
+            pump_duty: self.pump_duty.unwrap_or(f32::NAN),
+            heater_duty: self.heater_duty.unwrap_or(f32::NAN),
+        }
+    }
+
+    fn message(&self, topic: Topic) -> Option<Messages> {
+        match topic {
+            Topic::BoilerTemperature => self
+                .boiler
+                .map(|temperature| Messages::ThermocoupleReadout { temperature }),
+            Topic::Pressure => self.pressure.map(|pressure| Messages::PressureReadout { pressure }),
+            Topic::RtcTime => self
+                .rtc_time
+                .map(|(hour, minute, second)| Messages::RtcTime { hour, minute, second }),
+            Topic::Samples => None,
+        }
+    }
+}
+
+/// Updates the latest readings, called by the sensor tasks after every read.
+pub fn record(update: impl FnOnce(&mut Readings)) {
+    LATEST.lock(|latest| {
+        let mut readings = latest.get();
+        update(&mut readings);
+        latest.set(readings);
+    });
+}
+
+pub fn latest() -> Readings {
+    LATEST.lock(|latest| latest.get())
+}
+
+/// The telemetry queued for the client on `origin`.
+pub fn queue(origin: Origin) -> &'static Channel<CriticalSectionRawMutex, Messages, 8> {
+    &TELEMETRY[origin.index()]
+}
+
+pub fn subscribe(origin: Origin, topic: Topic, interval_ms: u32) -> Result<(), SubscribeError> {
+    let now = Instant::now().as_millis();
+    SCHEDULERS.lock(|s| s.borrow_mut()[origin.index()].subscribe(topic, interval_ms, now))?;
+    SUBSCRIPTIONS_CHANGED.signal(());
+    Ok(())
+}
+
+pub fn unsubscribe(origin: Origin, topic: Topic) {
+    SCHEDULERS.lock(|s| s.borrow_mut()[origin.index()].unsubscribe(topic));
+    SUBSCRIPTIONS_CHANGED.signal(());
+}
+
+/// Drops every subscription of `origin`, e.g. when its client disconnects. Telemetry still
+/// queued for it is dropped too, so the next client does not get it.
+pub fn unsubscribe_all(origin: Origin) {
+    SCHEDULERS.lock(|s| s.borrow_mut()[origin.index()].unsubscribe_all());
+    while queue(origin).try_receive().is_ok() {}
+    SUBSCRIPTIONS_CHANGED.signal(());
+}
+
+/// Selects how [Topic::Samples] batches are sent to `origin`, starting with the next one.
+pub fn set_encoding(origin: Origin, encoding: TelemetryEncoding) {
+    ENCODINGS.lock(|e| {
+        let mut encodings = e.get();
+        encodings[origin.index()] = encoding;
+        e.set(encodings);
+    });
+}
+
+/// Publishes the latest reading of each topic a link subscribed to whenever it is due. Samples
+/// are batched into a [TelemetryFrame] per link that is sent once full.
+#[embassy_executor::task]
+pub async fn publish_telemetry() {
+    let mut frames = [TelemetryFrame::new(), TelemetryFrame::new()];
+    let mut encoders = [DeltaEncoder::default(), DeltaEncoder::default()];
+    loop {
+        let next_due = SCHEDULERS.lock(|s| s.borrow().iter().filter_map(Scheduler::next_due).min());
+        let wake = async {
+            match next_due {
+                Some(due) => Timer::at(Instant::from_millis(due)).await,
+                None => core::future::pending().await,
+            }
+        };
+
+        if let Either::First(_) = select(SUBSCRIPTIONS_CHANGED.wait(), wake).await {
+            continue;
+        }
+
+        let now = Instant::now();
+        let readings = latest();
+        for origin in Origin::ALL {
+            let link = origin.index();
+            let (frame, encoder) = (&mut frames[link], &mut encoders[link]);
+            while let Some(topic) = SCHEDULERS.lock(|s| s.borrow_mut()[link].poll(now.as_millis())) {
+                if topic == Topic::Samples {
+                    if frame.push(now.as_micros(), readings.channels()).is_err() {
+                        // The sample is too far from the first of the batch, start a new one
+                        frame.clear();
+                        let _ = frame.push(now.as_micros(), readings.channels());
+                    }
+                    if frame.is_full() {
+                        let batch = core::mem::replace(frame, TelemetryFrame::new());
+                        let message = match ENCODINGS.lock(|e| e.get()[link]) {
+                            TelemetryEncoding::Full => Messages::Telemetry(batch),
+                            TelemetryEncoding::Delta => Messages::CompactTelemetry(encoder.encode(&batch)),
+                        };
+                        if queue(origin).try_send(message).is_err() {
+                            crate::mirror!(debug, "Telemetry queue full, dropped a frame");
+                            // The client cannot decode deltas against the lost frame
+                            encoder.force_keyframe();
+                        }
+                    }
+                    continue;
+                }
+
+                let Some(message) = readings.message(topic) else {
+                    continue;
+                };
+                if queue(origin).try_send(message).is_err() {
+                    crate::mirror!(debug, "Telemetry queue full, dropped {:?}", topic);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use axis_protocol::transport::FramedStream;
+use embassy_rp::peripherals::USB;
+use embassy_rp::usb::Driver;
+use embassy_usb::class::cdc_acm::{Receiver, Sender};
+use embassy_usb::driver::EndpointError;
+use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+
+use crate::client_communicator::{MAX_FRAME_SIZE, MAX_PACKET_SIZE};
+
+/// The protocol over USB CDC.
+pub type UsbTransport<'d> = FramedStream<CdcStream<'d>, MAX_FRAME_SIZE>;
+
+#[derive(Debug, defmt::Format)]
+pub struct UsbError(pub EndpointError);
+
+impl embedded_io_async::Error for UsbError {
+    fn kind(&self) -> ErrorKind {
+        match self.0 {
+            EndpointError::BufferOverflow => ErrorKind::OutOfMemory,
+            EndpointError::Disabled => ErrorKind::NotConnected,
+        }
+    }
+}
+
+/// A USB CDC class as a byte stream, one USB packet at a time.
+pub struct CdcStream<'d> {
+    sender: Sender<'d, Driver<'d, USB>>,
+    receiver: Receiver<'d, Driver<'d, USB>>,
+    /// The last packet written was full, so the host waits for more until it gets a shorter one.
+    needs_terminator: bool,
+}
+
+impl<'d> CdcStream<'d> {
+    pub fn new(sender: Sender<'d, Driver<'d, USB>>, receiver: Receiver<'d, Driver<'d, USB>>) -> Self {
+        Self {
+            sender,
+            receiver,
+            needs_terminator: false,
+        }
+    }
+
+    /// Waits until the host has configured the device.
+    pub async fn wait_connection(&mut self) {
+        self.receiver.wait_connection().await;
+    }
+}
+
+impl ErrorType for CdcStream<'_> {
+    type Error = UsbError;
+}
+
+impl Read for CdcStream<'_> {
+    /// `buf` must hold at least [MAX_PACKET_SIZE] bytes.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, UsbError> {
+        self.receiver.read_packet(buf).await.map_err(UsbError)
+    }
+}
+
+impl Write for CdcStream<'_> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, UsbError> {
+        let len = buf.len().min(MAX_PACKET_SIZE);
+        self.sender.write_packet(&buf[..len]).await.map_err(UsbError)?;
+        self.needs_terminator = len == MAX_PACKET_SIZE;
+        Ok(len)
+    }
+
+    /// Ends the transfer with a zero length packet when the last packet was full, so the host
+    /// does not wait for more data.
+    async fn flush(&mut self) -> Result<(), UsbError> {
+        if self.needs_terminator {
+            self.sender.write_packet(&[]).await.map_err(UsbError)?;
+            self.needs_terminator = false;
+        }
+        Ok(())
+    }
+}
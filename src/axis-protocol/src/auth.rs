@@ -0,0 +1,395 @@
+//! Optional authenticated sessions for transports anyone on the network can reach. Over USB the
+//! client is trusted, and it provisions a pre-shared [Key] with
+//! [Commands::ProvisionKey](crate::messages::Commands::ProvisionKey).
+//!
+//! A session is opened with a challenge-response exchange, proving on both sides that the peer
+//! holds the key without sending it:
+//!
+//! 1. The client sends [Commands::Challenge](crate::messages::Commands::Challenge) with a random
+//!    nonce, and the host (MCU) answers with
+//!    [Responses::Challenge](crate::messages::Responses::Challenge) carrying its own.
+//! 2. The client sends [Commands::Authenticate](crate::messages::Commands::Authenticate) with a
+//!    [Proof] over both nonces, and the host (MCU) answers with
+//!    [Responses::Authenticated](crate::messages::Responses::Authenticated) and its own proof.
+//!
+//! From then on every packet is sealed into a [MessageId::Sealed] packet: the original packet
+//! followed by a truncated HMAC-SHA256 [Tag]. Each direction has its own key derived from the
+//! nonces, so packets cannot be reflected back to their sender. The tag covers the
+//! [MessageHeader] sequence extended to 64 bits, and the receiver drops sequences it has already
+//! seen, see [Opener]. The host (MCU) switches to sealed packets after sending its
+//! `Authenticated` response, the client after receiving it.
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{MessageHeader, MessageId};
+
+pub type Key = [u8; 32];
+pub type Nonce = [u8; 16];
+pub type Proof = [u8; 32];
+
+pub const TAG_LEN: usize = 16;
+pub type Tag = [u8; TAG_LEN];
+
+/// Bytes a sealed packet adds: the outer header and the tag.
+pub const SEAL_OVERHEAD: usize = 3 + TAG_LEN;
+
+/// Sequences older than the newest one by this much are dropped as replays.
+const REPLAY_WINDOW: u64 = 64;
+
+const CLIENT_PROOF: &[u8] = b"axis client proof";
+const HOST_PROOF: &[u8] = b"axis host proof";
+const CLIENT_TO_HOST: &[u8] = b"axis client to host";
+const HOST_TO_CLIENT: &[u8] = b"axis host to client";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum AuthError {
+    /// No key was provisioned, so no session can be opened.
+    NotProvisioned = 0,
+    /// [Commands::Authenticate](crate::messages::Commands::Authenticate) was sent without a
+    /// challenge before it.
+    NoChallenge = 1,
+    /// The peer's proof does not match, it holds a different key.
+    BadProof = 2,
+    /// The command needs an authenticated session.
+    Required = 3,
+}
+
+/// Why a sealed packet was dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SealError {
+    /// The packet is too short to hold a tag.
+    Truncated,
+    /// The packet inside does not match its outer header.
+    Malformed,
+    /// The tag does not match, the packet was forged or corrupted.
+    BadTag,
+    /// The sequence was already received, or is too old to tell.
+    Replayed,
+    /// The output buffer cannot hold the sealed packet.
+    BufferTooSmall,
+}
+
+fn mac(key: &[u8], parts: &[&[u8]]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+fn derive(key: &Key, label: &[u8], client_nonce: &Nonce, host_nonce: &Nonce) -> [u8; 32] {
+    mac(key, &[label, client_nonce, host_nonce]).finalize().into_bytes().into()
+}
+
+fn keys(key: &Key, client_nonce: &Nonce, host_nonce: &Nonce) -> (Key, Key) {
+    (
+        derive(key, CLIENT_TO_HOST, client_nonce, host_nonce),
+        derive(key, HOST_TO_CLIENT, client_nonce, host_nonce),
+    )
+}
+
+/// The host (MCU)'s side of the handshake.
+pub struct Authenticator {
+    key: Option<Key>,
+    nonces: Option<(Nonce, Nonce)>,
+}
+
+impl Authenticator {
+    pub const fn new() -> Self {
+        Self { key: None, nonces: None }
+    }
+
+    /// Replaces the pre-shared key, or removes it with `None`. Cancels a pending challenge.
+    pub fn provision(&mut self, key: Option<Key>) {
+        self.key = key;
+        self.nonces = None;
+    }
+
+    pub fn is_provisioned(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Answers a challenge with `host_nonce`, which must come from a random number generator.
+    pub fn challenge(&mut self, client_nonce: Nonce, host_nonce: Nonce) -> Result<Nonce, AuthError> {
+        if self.key.is_none() {
+            return Err(AuthError::NotProvisioned);
+        }
+        self.nonces = Some((client_nonce, host_nonce));
+        Ok(host_nonce)
+    }
+
+    /// Checks the client's proof, returning the host (MCU)'s proof to answer with and the
+    /// session. The challenge is used up either way.
+    pub fn authenticate(&mut self, proof: &Proof) -> Result<(Proof, Session), AuthError> {
+        let key = self.key.as_ref().ok_or(AuthError::NotProvisioned)?;
+        let (client_nonce, host_nonce) = self.nonces.take().ok_or(AuthError::NoChallenge)?;
+
+        mac(key, &[CLIENT_PROOF, &client_nonce, &host_nonce])
+            .verify_slice(proof)
+            .map_err(|_| AuthError::BadProof)?;
+
+        let (client_to_host, host_to_client) = keys(key, &client_nonce, &host_nonce);
+        Ok((
+            derive(key, HOST_PROOF, &client_nonce, &host_nonce),
+            Session::new(host_to_client, client_to_host),
+        ))
+    }
+}
+
+impl Default for Authenticator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The client's side of the handshake.
+///
+/// ```
+/// use axis_protocol::auth::{Authenticator, ClientHandshake};
+/// use axis_protocol::messages::Commands;
+/// use axis_protocol::{encode_packet, MessageHeader, MessageId};
+///
+/// let key = [7u8; 32];
+/// let mut host = Authenticator::new();
+/// host.provision(Some(key));
+///
+/// let mut client = ClientHandshake::new(key, [1; 16]);
+/// let host_nonce = host.challenge(client.nonce(), [2; 16]).unwrap();
+/// let proof = client.respond(host_nonce);
+/// let (host_proof, mut host_session) = host.authenticate(&proof).unwrap();
+/// let mut client_session = client.finish(&host_proof).unwrap();
+///
+/// let mut buf = [0u8; 64];
+/// let mut sealed = [0u8; 64];
+/// let packet = encode_packet(MessageHeader::new(0, MessageId::Command), &Commands::StartShot, &mut buf).unwrap();
+/// let sealed = client_session.sealer.seal(packet, &mut sealed).unwrap();
+///
+/// let (header, body) = axis_protocol::decode_header(sealed).unwrap();
+/// assert_eq!(host_session.opener.open(header, body), Ok(&packet[..]));
+/// // The same packet again is a replay
+/// assert!(host_session.opener.open(header, body).is_err());
+/// ```
+pub struct ClientHandshake {
+    key: Key,
+    client_nonce: Nonce,
+    host_nonce: Option<Nonce>,
+}
+
+impl ClientHandshake {
+    /// Starts a handshake with `client_nonce`, which must come from a random number generator.
+    pub fn new(key: Key, client_nonce: Nonce) -> Self {
+        Self {
+            key,
+            client_nonce,
+            host_nonce: None,
+        }
+    }
+
+    /// The nonce to send with [Commands::Challenge](crate::messages::Commands::Challenge).
+    pub fn nonce(&self) -> Nonce {
+        self.client_nonce
+    }
+
+    /// The proof to send with [Commands::Authenticate](crate::messages::Commands::Authenticate).
+    pub fn respond(&mut self, host_nonce: Nonce) -> Proof {
+        self.host_nonce = Some(host_nonce);
+        derive(&self.key, CLIENT_PROOF, &self.client_nonce, &host_nonce)
+    }
+
+    /// Checks the host (MCU)'s proof, returning the session.
+    pub fn finish(self, proof: &Proof) -> Result<Session, AuthError> {
+        let host_nonce = self.host_nonce.ok_or(AuthError::NoChallenge)?;
+
+        mac(&self.key, &[HOST_PROOF, &self.client_nonce, &host_nonce])
+            .verify_slice(proof)
+            .map_err(|_| AuthError::BadProof)?;
+
+        let (client_to_host, host_to_client) = keys(&self.key, &self.client_nonce, &host_nonce);
+        Ok(Session::new(client_to_host, host_to_client))
+    }
+}
+
+/// Both directions of an authenticated session. The halves can be moved to separate tasks.
+pub struct Session {
+    pub sealer: Sealer,
+    pub opener: Opener,
+}
+
+impl Session {
+    fn new(send_key: Key, receive_key: Key) -> Self {
+        Self {
+            sealer: Sealer {
+                key: send_key,
+                epoch: 0,
+                last: None,
+            },
+            opener: Opener {
+                key: receive_key,
+                newest: None,
+                seen: 0,
+            },
+        }
+    }
+}
+
+/// Seals outgoing packets.
+///
+/// The receiver rebuilds the sender's 64 bit sequence from the order packets arrive in, so every
+/// kind of packet sent in a session has to take its sequence number from the same counter:
+///
+/// ```
+/// use axis_protocol::auth::{Authenticator, ClientHandshake};
+/// use axis_protocol::heartbeat::{encode_heartbeat, Heartbeat};
+/// use axis_protocol::messages::Commands;
+/// use axis_protocol::reliability::encode_ack;
+/// use axis_protocol::{decode_header, encode_packet, MessageHeader, MessageId};
+///
+/// let key = [7u8; 32];
+/// let mut host = Authenticator::new();
+/// host.provision(Some(key));
+/// let mut client = ClientHandshake::new(key, [1; 16]);
+/// let proof = client.respond(host.challenge(client.nonce(), [2; 16]).unwrap());
+/// let (host_proof, mut host_session) = host.authenticate(&proof).unwrap();
+/// let mut client_session = client.finish(&host_proof).unwrap();
+///
+/// let mut buf = [0u8; 64];
+/// let mut sealed = [0u8; 64];
+/// let mut sequence = 5u8;
+/// for uptime_ms in 0..4 {
+///     let packet = if uptime_ms % 2 == 0 {
+///         encode_packet(MessageHeader::new(sequence, MessageId::Command), &Commands::Ping, &mut buf)
+///     } else {
+///         encode_heartbeat(sequence, Heartbeat { uptime_ms }, &mut buf)
+///     }
+///     .unwrap();
+///     sequence = sequence.wrapping_add(1);
+///
+///     let sealed = client_session.sealer.seal(packet, &mut sealed).unwrap();
+///     let (header, body) = decode_header(sealed).unwrap();
+///     assert_eq!(host_session.opener.open(header, body), Ok(&packet[..]));
+/// }
+///
+/// // Acks are numbered the same way, so a captured one cannot be replayed
+/// let ack = encode_ack(0, 5, &mut buf).unwrap();
+/// let sealed = host_session.sealer.seal(ack, &mut sealed).unwrap();
+/// let (header, body) = decode_header(sealed).unwrap();
+/// assert!(client_session.opener.open(header, body).is_ok());
+/// assert!(client_session.opener.open(header, body).is_err());
+/// ```
+pub struct Sealer {
+    key: Key,
+    epoch: u64,
+    last: Option<u8>,
+}
+
+impl Sealer {
+    /// Seals `packet`, as serialized by [encode_packet](crate::encode_packet), into `buf`.
+    /// Packets must be sealed in the order of their sequence numbers, and a retransmission
+    /// must resend the sealed packet rather than sealing it again.
+    pub fn seal<'a>(&mut self, packet: &[u8], buf: &'a mut [u8]) -> Result<&'a mut [u8], SealError> {
+        let (header, _) = crate::decode_header(packet).map_err(|_| SealError::Malformed)?;
+        let sequence = header.get_sequence();
+        if self.last.is_some_and(|last| sequence <= last) {
+            self.epoch += 1;
+        }
+        self.last = Some(sequence);
+        let counter = self.epoch << 8 | sequence as u64;
+
+        let outer = MessageHeader::new(sequence, MessageId::Sealed);
+        let header_len = postcard::to_slice(&outer, buf).map_err(|_| SealError::BufferTooSmall)?.len();
+        let len = header_len + packet.len() + TAG_LEN;
+        if buf.len() < len {
+            return Err(SealError::BufferTooSmall);
+        }
+
+        buf[header_len..len - TAG_LEN].copy_from_slice(packet);
+        let tag = mac(&self.key, &[&counter.to_le_bytes(), packet]).finalize().into_bytes();
+        buf[len - TAG_LEN..len].copy_from_slice(&tag[..TAG_LEN]);
+        Ok(&mut buf[..len])
+    }
+}
+
+/// Opens incoming packets, dropping forged and replayed ones.
+///
+/// The sender's 64 bit sequence is reconstructed as the one closest to the newest received, so
+/// the session only survives losing fewer than 128 packets in a row.
+pub struct Opener {
+    key: Key,
+    newest: Option<u64>,
+    /// Bit `n` is set if `newest - n` was received.
+    seen: u64,
+}
+
+impl Opener {
+    /// Checks the body of a [MessageId::Sealed] packet, returning the packet inside.
+    pub fn open<'a>(&mut self, header: MessageHeader, body: &'a [u8]) -> Result<&'a [u8], SealError> {
+        let Some(split) = body.len().checked_sub(TAG_LEN) else {
+            return Err(SealError::Truncated);
+        };
+        let (packet, tag) = body.split_at(split);
+
+        let (inner, _) = crate::decode_header(packet).map_err(|_| SealError::Truncated)?;
+        if inner.get_sequence() != header.get_sequence() {
+            return Err(SealError::Malformed);
+        }
+
+        let counter = self.extend(header.get_sequence());
+        if self.was_seen(counter) {
+            return Err(SealError::Replayed);
+        }
+
+        mac(&self.key, &[&counter.to_le_bytes(), packet])
+            .verify_truncated_left(tag)
+            .map_err(|_| SealError::BadTag)?;
+
+        self.mark_seen(counter);
+        Ok(packet)
+    }
+
+    fn extend(&self, sequence: u8) -> u64 {
+        let Some(newest) = self.newest else {
+            return sequence as u64;
+        };
+
+        let candidate = (newest & !0xff) | sequence as u64;
+        [candidate.checked_sub(256), Some(candidate), candidate.checked_add(256)]
+            .into_iter()
+            .flatten()
+            .min_by_key(|c| c.abs_diff(newest))
+            .unwrap_or(candidate)
+    }
+
+    fn was_seen(&self, counter: u64) -> bool {
+        match self.newest {
+            Some(newest) if counter <= newest => {
+                let age = newest - counter;
+                age >= REPLAY_WINDOW || self.seen & (1 << age) != 0
+            }
+            _ => false,
+        }
+    }
+
+    fn mark_seen(&mut self, counter: u64) {
+        match self.newest {
+            Some(newest) if counter <= newest => self.seen |= 1 << (newest - counter),
+            Some(newest) => {
+                let shift = counter - newest;
+                self.seen = if shift >= REPLAY_WINDOW { 0 } else { self.seen << shift };
+                self.seen |= 1;
+                self.newest = Some(counter);
+            }
+            None => {
+                self.seen = 1;
+                self.newest = Some(counter);
+            }
+        }
+    }
+}
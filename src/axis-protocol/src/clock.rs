@@ -0,0 +1,198 @@
+//! Wall-clock time of the host (MCU), kept by its battery backed RTC.
+//!
+//! [Commands::ReadClock](crate::messages::Commands::ReadClock) and
+//! [Commands::SetClock](crate::messages::Commands::SetClock) read and set it in seconds since
+//! the Unix epoch, always in UTC.
+//!
+//! Timestamps in telemetry, events and logs are microseconds since the host (MCU) booted
+//! instead, as the RTC only resolves seconds and may not be set at all.
+//! [Commands::SyncClock](crate::messages::Commands::SyncClock) measures a [ClockOffset] that
+//! maps those onto the client's clock.
+
+use serde::{Deserialize, Serialize};
+
+/// Earliest time the RTC can hold, 2000-01-01T00:00:00Z.
+pub const MIN_UNIX_TIME: u64 = 946_684_800;
+/// Latest time the RTC can hold, 2199-12-31T23:59:59Z.
+pub const MAX_UNIX_TIME: u64 = 7_258_118_399;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+/// Days from 0000-03-01 to 1970-01-01 in the proleptic Gregorian calendar.
+const UNIX_EPOCH_DAYS: u64 = 719_468;
+const DAYS_PER_ERA: u64 = 146_097;
+
+/// Answer to [Commands::ReadClock](crate::messages::Commands::ReadClock).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct ClockReading {
+    /// Seconds since the Unix epoch, or `None` if the RTC was never set, lost power or does
+    /// not respond.
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub unix_time: Option<u64>,
+    /// Microseconds since the host (MCU) booted, when the time was read.
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub uptime_us: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum ClockError {
+    /// The RTC does not respond.
+    Unavailable = 0,
+    /// The time is outside of [MIN_UNIX_TIME] and [MAX_UNIX_TIME].
+    OutOfRange = 1,
+}
+
+/// A calendar date and time of day in UTC, as stored by the RTC.
+///
+/// ```
+/// use axis_protocol::clock::UtcDateTime;
+///
+/// let time = UtcDateTime::from_unix(1_709_210_096);
+/// assert_eq!(time, UtcDateTime { year: 2024, month: 2, day: 29, hour: 12, minute: 34, second: 56 });
+/// assert_eq!(time.weekday(), 4);
+/// assert_eq!(time.to_unix(), Some(1_709_210_096));
+///
+/// assert_eq!(UtcDateTime { day: 30, ..time }.to_unix(), None);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UtcDateTime {
+    pub year: u16,
+    /// 1 to 12.
+    pub month: u8,
+    /// 1 to 31.
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl UtcDateTime {
+    pub fn from_unix(unix_time: u64) -> Self {
+        let days = unix_time / SECONDS_PER_DAY;
+        let seconds = unix_time % SECONDS_PER_DAY;
+
+        // Civil from days, counting years from March so the leap day comes last
+        let days = days + UNIX_EPOCH_DAYS;
+        let era = days / DAYS_PER_ERA;
+        let day_of_era = days % DAYS_PER_ERA;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+        let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+        let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+        }
+    }
+
+    /// Seconds since the Unix epoch, or `None` if a field is out of range or the time is
+    /// before the epoch.
+    pub fn to_unix(&self) -> Option<u64> {
+        if self.year < 1970
+            || !(1..=12).contains(&self.month)
+            || self.day < 1
+            || self.day > days_in_month(self.year, self.month)
+            || self.hour > 23
+            || self.minute > 59
+            || self.second > 59
+        {
+            return None;
+        }
+
+        let month = u64::from(self.month);
+        let year = u64::from(self.year) - u64::from(month <= 2);
+        let era = year / 400;
+        let year_of_era = year % 400;
+        let month_from_march = (month + 9) % 12;
+        let day_of_year = (153 * month_from_march + 2) / 5 + u64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * DAYS_PER_ERA + day_of_era - UNIX_EPOCH_DAYS;
+
+        let seconds = u64::from(self.hour) * 3600 + u64::from(self.minute) * 60 + u64::from(self.second);
+        Some(days * SECONDS_PER_DAY + seconds)
+    }
+
+    /// Day of the week, from 1 for Monday to 7 for Sunday.
+    pub fn weekday(&self) -> u8 {
+        let days = self.to_unix().unwrap_or(0) / SECONDS_PER_DAY;
+        // 1970-01-01 was a Thursday
+        ((days + 3) % 7 + 1) as u8
+    }
+}
+
+// is_multiple_of is newer than the toolchain the firmware is built with
+#[allow(clippy::manual_is_multiple_of)]
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Maps host (MCU) uptime onto the client's clock, measured from one
+/// [Commands::SyncClock](crate::messages::Commands::SyncClock) exchange.
+///
+/// The host (MCU) is assumed to have taken its timestamp halfway through the round trip, so the
+/// mapping is off by at most half of [round_trip_us](Self::round_trip_us). Measuring a few times
+/// and keeping the offset with the shortest round trip gives the best estimate.
+///
+/// ```
+/// use axis_protocol::clock::ClockOffset;
+///
+/// // Sent at 1000, answered with an uptime of 300, received at 1200
+/// let offset = ClockOffset::measure(1000, 1200, 300);
+/// assert_eq!(offset.round_trip_us, 200);
+/// assert_eq!(offset.to_client_us(300), 1100);
+/// assert_eq!(offset.to_client_us(5300), 6100);
+///
+/// let slower = ClockOffset::measure(2000, 2600, 1250);
+/// assert_eq!(offset.best(slower), offset);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ClockOffset {
+    /// Microseconds to add to an uptime to get the client's time.
+    pub offset_us: i64,
+    pub round_trip_us: u64,
+}
+
+impl ClockOffset {
+    /// Measures the offset from a request sent at `sent_us` and answered with `uptime_us` at
+    /// `received_us`, both on the client's clock.
+    pub fn measure(sent_us: u64, received_us: u64, uptime_us: u64) -> Self {
+        let round_trip_us = received_us.saturating_sub(sent_us);
+        let midpoint_us = sent_us + round_trip_us / 2;
+        Self {
+            offset_us: (midpoint_us as i64).wrapping_sub(uptime_us as i64),
+            round_trip_us,
+        }
+    }
+
+    /// The client's time at `uptime_us` on the host (MCU).
+    pub fn to_client_us(&self, uptime_us: u64) -> u64 {
+        (uptime_us as i64).saturating_add(self.offset_us).max(0) as u64
+    }
+
+    /// Whichever of the two offsets was measured more precisely.
+    pub fn best(self, other: Self) -> Self {
+        if other.round_trip_us < self.round_trip_us {
+            other
+        } else {
+            self
+        }
+    }
+}
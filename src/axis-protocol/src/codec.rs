@@ -0,0 +1,266 @@
+//! [tokio_util::codec] support for the framed byte stream, so a host can wrap any
+//! `AsyncRead`/`AsyncWrite` in a `Framed` and exchange whole [Packet]s. Fragments are split and
+//! reassembled by the codec, and sealed in an authenticated [Session] once one is set.
+//!
+//! Over a [Transport](crate::transport::Transport), which frames packets itself, the same steps
+//! are available one packet at a time: [seal](PacketCodec::seal), [split](PacketCodec::split)
+//! and [decode_packet](PacketCodec::decode_packet).
+
+use std::boxed::Box;
+use std::fmt;
+use std::io;
+use std::vec::Vec;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::auth::{Opener, SealError, Sealer, Session, SEAL_OVERHEAD};
+pub use crate::decode::{Body, Packet};
+use crate::decode::{decode_packet, open, DecodeError, DecodeStats, Decoded};
+use crate::fragment::{self, FragmentError, Fragments, Reassembler, MAX_FRAGMENT_LEN};
+use crate::framing::{self, FeedResult, FrameDecoder, FrameError};
+use crate::MessageHeader;
+
+/// Largest packet the codec reassembles.
+pub const MAX_PACKET_LEN: usize = 4096;
+
+const MAX_FRAME_LEN: usize = framing::max_frame_len(MAX_PACKET_LEN);
+
+#[derive(Debug)]
+pub enum CodecError {
+    Io(io::Error),
+    /// The packet could not be serialized.
+    Serialize(postcard::Error),
+    Frame(FrameError),
+    Fragment(FragmentError),
+    Seal(SealError),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Io(e) => write!(f, "I/O error: {e}"),
+            CodecError::Serialize(e) => write!(f, "failed to serialize packet: {e}"),
+            CodecError::Frame(e) => write!(f, "failed to frame packet: {e:?}"),
+            CodecError::Fragment(e) => write!(f, "failed to fragment packet: {e:?}"),
+            CodecError::Seal(e) => write!(f, "failed to seal packet: {e:?}"),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<io::Error> for CodecError {
+    fn from(e: io::Error) -> Self {
+        CodecError::Io(e)
+    }
+}
+
+/// Encodes and decodes [Packet]s.
+///
+/// A corrupted frame, a packet that does not deserialize or an incomplete fragmented transfer
+/// is dropped and counted, see [stats](Self::stats), and decoding continues with the next
+/// frame. Only I/O errors end a stream.
+///
+/// ```
+/// use axis_protocol::codec::{Body, Packet, PacketCodec};
+/// use axis_protocol::messages::Messages;
+/// use axis_protocol::units::Celsius;
+/// use bytes::BytesMut;
+/// use tokio_util::codec::{Decoder, Encoder};
+///
+/// let mut codec = PacketCodec::new();
+/// let mut stream = BytesMut::new();
+///
+/// let readout = Messages::ThermocoupleReadout { temperature: Celsius(93.5) };
+/// codec.encode(Packet::new(0, Body::Message(readout)), &mut stream).unwrap();
+///
+/// let packet = codec.decode(&mut stream).unwrap().unwrap();
+/// assert!(matches!(
+///     packet.body.into_message(),
+///     Some(Messages::ThermocoupleReadout { temperature }) if temperature == Celsius(93.5)
+/// ));
+/// assert!(codec.decode(&mut stream).unwrap().is_none());
+/// ```
+pub struct PacketCodec {
+    decoder: Box<FrameDecoder<MAX_FRAME_LEN>>,
+    reassembler: Box<Reassembler<MAX_PACKET_LEN>>,
+    transfer: u8,
+    stats: DecodeStats,
+    sealer: Option<Sealer>,
+    opener: Option<Opener>,
+}
+
+impl PacketCodec {
+    pub fn new() -> Self {
+        Self {
+            decoder: Box::new(FrameDecoder::new()),
+            reassembler: Box::new(Reassembler::new()),
+            transfer: 0,
+            stats: DecodeStats::new(),
+            sealer: None,
+            opener: None,
+        }
+    }
+
+    /// Number of frames and packets dropped while decoding.
+    pub fn dropped(&self) -> u64 {
+        self.stats.total() as u64
+    }
+
+    /// Frames and packets dropped while decoding, by reason.
+    pub fn stats(&self) -> &DecodeStats {
+        &self.stats
+    }
+
+    /// Seals every packet sent and requires every packet received to be sealed from now on, or
+    /// goes back to plain packets with `None`.
+    pub fn set_session(&mut self, session: Option<Session>) {
+        let (sealer, opener) = session.map(|s| (s.sealer, s.opener)).unzip();
+        self.sealer = sealer;
+        self.opener = opener;
+    }
+
+    /// Like [set_session](Self::set_session) for received packets only, when outgoing packets
+    /// are sealed elsewhere.
+    pub fn set_opener(&mut self, opener: Option<Opener>) {
+        self.opener = opener;
+    }
+
+    /// Drops any partially received frame or transfer, e.g. after the peer reconnects.
+    pub fn reset(&mut self) {
+        self.decoder.reset();
+        let _ = self.reassembler.abort();
+    }
+
+    /// Serializes `item`, sealed if a session is set. A sealed packet has to be resent as is
+    /// rather than sealed again, see [Sealer].
+    pub fn seal(&mut self, item: Packet) -> Result<Vec<u8>, CodecError> {
+        let header = item.header();
+        let packet = match &item.body {
+            Body::Message(message) => encode_body(header, message),
+            Body::Command(command) => encode_body(header, command),
+            Body::Response(response) => encode_body(header, response),
+            Body::Ack(acked) => encode_body(header, acked),
+            Body::Heartbeat(heartbeat) => encode_body(header, heartbeat),
+        }
+        .map_err(CodecError::Serialize)?;
+
+        match &mut self.sealer {
+            Some(sealer) => {
+                let mut sealed = vec![0; packet.len() + SEAL_OVERHEAD];
+                let len = sealer.seal(&packet, &mut sealed).map_err(CodecError::Seal)?.len();
+                sealed.truncate(len);
+                Ok(sealed)
+            }
+            None => Ok(packet),
+        }
+    }
+
+    /// Splits a packet returned by [seal](Self::seal) into the packets to send, one per frame.
+    pub fn split(&mut self, packet: &[u8]) -> Result<Vec<Vec<u8>>, CodecError> {
+        if !fragment::needs_fragmenting(packet.len()) {
+            return Ok(vec![packet.to_vec()]);
+        }
+
+        let mut fragments = Fragments::new(packet, self.transfer).map_err(CodecError::Fragment)?;
+        self.transfer = self.transfer.wrapping_add(1);
+
+        let mut buf = [0u8; MAX_FRAGMENT_LEN];
+        let mut packets = Vec::with_capacity(fragments.count() as usize);
+        while let Some(fragment) = fragments.next_into(&mut buf) {
+            packets.push(fragment.map_err(CodecError::Fragment)?.to_vec());
+        }
+        Ok(packets)
+    }
+
+    /// Decodes a packet taken out of a frame, reassembling fragments and opening sealed
+    /// packets. Returns `None` while a transfer is incomplete, or if the packet was dropped and
+    /// counted in [stats](Self::stats).
+    pub fn decode_packet(&mut self, packet: &[u8]) -> Option<Packet> {
+        match self.handle_packet(packet) {
+            Ok(packet) => packet,
+            Err(error) => {
+                self.stats.record(error);
+                None
+            }
+        }
+    }
+
+    /// Counts a frame the transport dropped, so [stats](Self::stats) covers every link.
+    pub fn record_frame_error(&mut self, error: FrameError) {
+        self.stats.record(error.into());
+    }
+
+    fn write_frame(packet: &[u8], dst: &mut BytesMut) -> Result<(), CodecError> {
+        let start = dst.len();
+        dst.put_bytes(0, framing::max_frame_len(packet.len()));
+        let len = framing::encode_frame(packet, &mut dst[start..]).map_err(CodecError::Frame)?;
+        dst.truncate(start + len);
+        Ok(())
+    }
+
+    /// Handles a packet taken out of a frame, reassembling fragments. Returns `Ok(None)` while
+    /// a transfer is still incomplete.
+    fn handle_packet(&mut self, packet: &[u8]) -> Result<Option<Packet>, DecodeError> {
+        let packet = match decode_packet(packet)? {
+            Decoded::Fragment { header, body } => match self.reassembler.push(header, body)? {
+                Some(packet) => packet,
+                None => return Ok(None),
+            },
+            decoded => return open(self.opener.as_mut(), decoded).map(Some),
+        };
+        open(self.opener.as_mut(), decode_packet(packet)?).map(Some)
+    }
+}
+
+impl Default for PacketCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for PacketCodec {
+    type Item = Packet;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Packet>, CodecError> {
+        while !src.is_empty() {
+            let (frame, consumed) = match self.decoder.feed(src) {
+                FeedResult::Consumed => (None, src.len()),
+                FeedResult::Frame { packet, remaining } => (Some(packet.to_vec()), src.len() - remaining.len()),
+                FeedResult::Error { error, remaining } => {
+                    self.stats.record(error.into());
+                    (None, src.len() - remaining.len())
+                }
+            };
+            src.advance(consumed);
+
+            let Some(frame) = frame else {
+                continue;
+            };
+            if let Some(packet) = self.decode_packet(&frame) {
+                return Ok(Some(packet));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Encoder<Packet> for PacketCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: Packet, dst: &mut BytesMut) -> Result<(), CodecError> {
+        let packet = self.seal(item)?;
+        for packet in self.split(&packet)? {
+            Self::write_frame(&packet, dst)?;
+        }
+        Ok(())
+    }
+}
+
+fn encode_body<T: serde::Serialize>(header: MessageHeader, body: &T) -> postcard::Result<Vec<u8>> {
+    let mut packet = postcard::to_allocvec(&header)?;
+    packet.extend_from_slice(&postcard::to_allocvec(body)?);
+    Ok(packet)
+}
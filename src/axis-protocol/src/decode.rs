@@ -0,0 +1,378 @@
+//! Single entry point for turning received bytes into packets, with a typed [DecodeError] for
+//! everything that can go wrong on the way. Decoding never panics, whatever the input.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{Opener, SealError};
+use crate::fragment::FragmentError;
+use crate::framing::FrameError;
+use crate::heartbeat::Heartbeat;
+use crate::messages::{Commands, Messages, Response};
+use crate::{MessageHeader, MessageId};
+
+/// A packet with its body deserialized.
+#[derive(Clone, Debug)]
+pub struct Packet {
+    pub sequence: u8,
+    pub body: Body,
+}
+
+#[derive(Clone, Debug)]
+// Like the messages it carries, a body is handled one at a time.
+#[allow(clippy::large_enum_variant)]
+pub enum Body {
+    Message(Messages),
+    Command(Commands),
+    Response(Response),
+    /// Acknowledges the reliable packet with this sequence number.
+    Ack(u8),
+    Heartbeat(Heartbeat),
+}
+
+/// Result of [decode_packet].
+#[derive(Clone, Debug)]
+// Returned once per packet and matched right away, so boxing the packet buys nothing.
+#[allow(clippy::large_enum_variant)]
+pub enum Decoded<'a> {
+    Packet(Packet),
+    /// Part of a larger packet, to be passed on to a [Reassembler](crate::fragment::Reassembler)
+    /// and decoded once complete.
+    Fragment { header: MessageHeader, body: &'a [u8] },
+    /// A packet sealed in an authenticated session, to be passed on to an
+    /// [Opener](crate::auth::Opener) and decoded once opened.
+    Sealed { header: MessageHeader, body: &'a [u8] },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum DecodeError {
+    /// The input ended before the packet did.
+    Truncated = 0,
+    /// The header names a message id this side does not know.
+    UnknownMessageId(u8) = 1,
+    /// The frame's CRC does not match its contents.
+    BadCrc = 2,
+    /// The frame or fragmented transfer is larger than the receive buffer.
+    Oversized = 3,
+    /// The body names a variant this side does not know, usually because the peer speaks a
+    /// newer protocol version.
+    ///
+    /// postcard reports every error raised by a type's own `Deserialize` impl the same way,
+    /// so a list or string longer than its capacity, or an invalid field value rejected by
+    /// serde, is counted here as well.
+    VersionMismatch = 4,
+    /// Anything else that does not deserialize, such as invalid framing or field values.
+    Malformed = 5,
+    /// A sealed packet was forged or replayed, or arrived outside an authenticated session.
+    Unauthenticated = 6,
+}
+
+impl Packet {
+    pub fn new(sequence: u8, body: Body) -> Self {
+        Self { sequence, body }
+    }
+
+    pub fn header(&self) -> MessageHeader {
+        MessageHeader::new(self.sequence, self.body.message_id())
+    }
+}
+
+impl Body {
+    pub fn message_id(&self) -> MessageId {
+        match self {
+            Body::Message(_) => MessageId::Message,
+            Body::Command(_) => MessageId::Command,
+            Body::Response(_) => MessageId::Response,
+            Body::Ack(_) => MessageId::Ack,
+            Body::Heartbeat(_) => MessageId::Heartbeat,
+        }
+    }
+
+    /// The message carried by this body, if it is one.
+    pub fn into_message(self) -> Option<Messages> {
+        match self {
+            Body::Message(message) => Some(message),
+            _ => None,
+        }
+    }
+}
+
+impl From<postcard::Error> for DecodeError {
+    fn from(e: postcard::Error) -> Self {
+        match e {
+            postcard::Error::DeserializeUnexpectedEnd => DecodeError::Truncated,
+            // Raised by serde for an out of range variant index, but also for capacity overflows
+            // and other custom errors, which postcard does not tell apart
+            postcard::Error::SerdeDeCustom => DecodeError::VersionMismatch,
+            _ => DecodeError::Malformed,
+        }
+    }
+}
+
+impl From<FrameError> for DecodeError {
+    fn from(e: FrameError) -> Self {
+        match e {
+            FrameError::Truncated => DecodeError::Truncated,
+            FrameError::Crc => DecodeError::BadCrc,
+            FrameError::Overflow | FrameError::BufferTooSmall => DecodeError::Oversized,
+            FrameError::Cobs => DecodeError::Malformed,
+        }
+    }
+}
+
+impl From<FragmentError> for DecodeError {
+    fn from(e: FragmentError) -> Self {
+        match e {
+            FragmentError::Incomplete { .. } => DecodeError::Truncated,
+            FragmentError::TooLarge | FragmentError::Overflow => DecodeError::Oversized,
+            FragmentError::Malformed | FragmentError::BufferTooSmall => DecodeError::Malformed,
+        }
+    }
+}
+
+impl From<SealError> for DecodeError {
+    fn from(e: SealError) -> Self {
+        match e {
+            SealError::Truncated => DecodeError::Truncated,
+            SealError::Malformed => DecodeError::Malformed,
+            SealError::BufferTooSmall => DecodeError::Oversized,
+            SealError::BadTag | SealError::Replayed => DecodeError::Unauthenticated,
+        }
+    }
+}
+
+/// Decodes a packet taken out of a frame.
+///
+/// ```
+/// use axis_protocol::decode::{decode_packet, Body, DecodeError, Decoded};
+/// use axis_protocol::messages::Commands;
+/// use axis_protocol::{encode_packet, MessageHeader, MessageId};
+///
+/// let mut buf = [0u8; 16];
+/// let packet = encode_packet(MessageHeader::new(7, MessageId::Command), &Commands::Ping, &mut buf).unwrap();
+/// assert!(matches!(
+///     decode_packet(packet),
+///     Ok(Decoded::Packet(p)) if p.sequence == 7 && matches!(p.body, Body::Command(Commands::Ping))
+/// ));
+///
+/// assert_eq!(decode_packet(&[]).unwrap_err(), DecodeError::Truncated);
+/// assert_eq!(decode_packet(&[0xff, 0x01]).unwrap_err(), DecodeError::UnknownMessageId(0xff));
+/// // Command 0x7f does not exist
+/// assert_eq!(decode_packet(&[0x01, 0x7f]).unwrap_err(), DecodeError::VersionMismatch);
+/// ```
+pub fn decode_packet(packet: &[u8]) -> Result<Decoded<'_>, DecodeError> {
+    fn body_of<T: DeserializeOwned>(body: &[u8]) -> Result<T, DecodeError> {
+        Ok(postcard::from_bytes(body)?)
+    }
+
+    let (header, body) = crate::decode_header(packet)?;
+    let message_id = header.get_message_id();
+    let message_id = MessageId::try_from(message_id).map_err(DecodeError::UnknownMessageId)?;
+
+    let body = match message_id {
+        MessageId::Message => Body::Message(body_of(body)?),
+        MessageId::Command => Body::Command(body_of(body)?),
+        MessageId::Response => Body::Response(body_of(body)?),
+        MessageId::Ack => Body::Ack(body_of(body)?),
+        MessageId::Heartbeat => Body::Heartbeat(body_of(body)?),
+        MessageId::Fragment => return Ok(Decoded::Fragment { header, body }),
+        MessageId::Sealed => return Ok(Decoded::Sealed { header, body }),
+    };
+    Ok(Decoded::Packet(Packet::new(header.get_sequence(), body)))
+}
+
+/// Opens `decoded` if it is sealed, which it must be exactly while a session is open, i.e. when
+/// `opener` is given. Fragments have to be reassembled and decoded again first.
+pub fn open(opener: Option<&mut Opener>, decoded: Decoded<'_>) -> Result<Packet, DecodeError> {
+    match (decoded, opener) {
+        (Decoded::Packet(packet), None) => Ok(packet),
+        (Decoded::Sealed { header, body }, Some(opener)) => match decode_packet(opener.open(header, body)?)? {
+            Decoded::Packet(packet) => Ok(packet),
+            // Only whole packets are sealed
+            _ => Err(DecodeError::Malformed),
+        },
+        // A fragment never holds another fragment
+        (Decoded::Fragment { .. }, _) => Err(DecodeError::Malformed),
+        _ => Err(DecodeError::Unauthenticated),
+    }
+}
+
+/// Number of decode errors seen in each category, and of packets the sender had to drop, for
+/// diagnostics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct DecodeStats {
+    pub truncated: u32,
+    pub unknown_message_id: u32,
+    pub bad_crc: u32,
+    pub oversized: u32,
+    pub version_mismatch: u32,
+    pub malformed: u32,
+    pub unauthenticated: u32,
+    /// Outbound packets dropped before they were completely sent, e.g. because the peer stopped
+    /// reading in the middle of a fragmented transfer. Not a decode error, so not part of
+    /// [total](Self::total).
+    pub send_dropped: u32,
+}
+
+impl DecodeStats {
+    pub const fn new() -> Self {
+        Self {
+            truncated: 0,
+            unknown_message_id: 0,
+            bad_crc: 0,
+            oversized: 0,
+            version_mismatch: 0,
+            malformed: 0,
+            unauthenticated: 0,
+            send_dropped: 0,
+        }
+    }
+
+    pub fn record(&mut self, error: DecodeError) {
+        let counter = match error {
+            DecodeError::Truncated => &mut self.truncated,
+            DecodeError::UnknownMessageId(_) => &mut self.unknown_message_id,
+            DecodeError::BadCrc => &mut self.bad_crc,
+            DecodeError::Oversized => &mut self.oversized,
+            DecodeError::VersionMismatch => &mut self.version_mismatch,
+            DecodeError::Malformed => &mut self.malformed,
+            DecodeError::Unauthenticated => &mut self.unauthenticated,
+        };
+        *counter = counter.saturating_add(1);
+    }
+
+    pub fn record_send_dropped(&mut self) {
+        self.send_dropped = self.send_dropped.saturating_add(1);
+    }
+
+    pub fn total(&self) -> u32 {
+        [
+            self.truncated,
+            self.unknown_message_id,
+            self.bad_crc,
+            self.oversized,
+            self.version_mismatch,
+            self.malformed,
+            self.unauthenticated,
+        ]
+        .iter()
+        .fold(0u32, |total, n| total.saturating_add(*n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{Authenticator, ClientHandshake};
+    use crate::delta::{CompactFrame, DeltaDecoder, MAX_COMPACT_LEN};
+    use crate::fragment::Reassembler;
+    use crate::framing::FrameDecoder;
+
+    /// xorshift64, so the inputs are the same on every run.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn coin(&mut self) -> bool {
+            self.next() & 1 == 0
+        }
+
+        fn fill(&mut self, buf: &mut [u8]) {
+            for byte in buf {
+                *byte = self.next() as u8;
+            }
+        }
+    }
+
+    fn opener() -> Opener {
+        let key = [7u8; 32];
+        let mut authenticator = Authenticator::new();
+        authenticator.provision(Some(key));
+        let mut client = ClientHandshake::new(key, [1u8; 16]);
+        authenticator.challenge(client.nonce(), [2u8; 16]).unwrap();
+        let (_, session) = authenticator.authenticate(&client.respond([2u8; 16])).unwrap();
+        session.opener
+    }
+
+    /// Runs `packet` through every decoding step a receiver takes, with and without a session.
+    fn decode_all(packet: &[u8], reassembler: &mut Reassembler<256>, opener: &mut Opener) {
+        for opener in [None, Some(&mut *opener)] {
+            let Ok(decoded) = decode_packet(packet) else {
+                continue;
+            };
+            if let Decoded::Fragment { header, body } = decoded {
+                if let Ok(Some(packet)) = reassembler.push(header, body) {
+                    let _ = decode_packet(packet).and_then(|decoded| open(opener, decoded));
+                }
+                continue;
+            }
+            let _ = open(opener, decoded);
+        }
+    }
+
+    #[test]
+    fn short_packets_never_panic() {
+        let mut reassembler = Reassembler::new();
+        let mut opener = opener();
+        decode_all(&[], &mut reassembler, &mut opener);
+        for a in 0..=255u8 {
+            decode_all(&[a], &mut reassembler, &mut opener);
+            for b in 0..=255u8 {
+                decode_all(&[a, b], &mut reassembler, &mut opener);
+                // Every message id with every first body byte, and a few more
+                decode_all(&[b, a, 0, 1, 0xff], &mut reassembler, &mut opener);
+            }
+        }
+    }
+
+    #[test]
+    fn random_packets_never_panic() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let mut reassembler = Reassembler::new();
+        let mut opener = opener();
+        let mut frames = FrameDecoder::<128>::new();
+        let mut buf = [0u8; 96];
+        for _ in 0..200_000 {
+            let len = rng.next() as usize % buf.len();
+            let packet = &mut buf[..len];
+            rng.fill(packet);
+            // Mostly known message ids, so the bodies get deserialized
+            if let Some(id) = packet.get_mut(1) {
+                *id %= 8;
+            }
+            decode_all(packet, &mut reassembler, &mut opener);
+            let _ = frames.feed(packet);
+        }
+    }
+
+    #[test]
+    fn random_compact_frames_never_panic() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        let mut decoder = DeltaDecoder::new();
+        let mut data = [0u8; MAX_COMPACT_LEN];
+        for _ in 0..20_000 {
+            let len = rng.next() as usize % (data.len() + 1);
+            rng.fill(&mut data[..len]);
+            let compact = CompactFrame {
+                sequence: rng.next() as u16 % 4,
+                keyframe: rng.coin(),
+                base_timestamp_us: if rng.coin() { u64::MAX - rng.next() % 1000 } else { rng.next() },
+                data: heapless::Vec::from_slice(&data[..len]).unwrap(),
+            };
+            if let Ok(frame) = decoder.decode(&compact) {
+                let _ = frame.iter().count();
+            }
+        }
+    }
+}
@@ -0,0 +1,437 @@
+//! Compact encoding of [TelemetryFrame]s for high-rate streams, selected with
+//! [TelemetryEncoding::Delta](crate::telemetry::TelemetryEncoding::Delta).
+//!
+//! Every channel is converted to fixed point, see [Resolution], and each sample is stored as
+//! the zigzag varint difference to the sample before it. A [CompactFrame] continues from the
+//! last sample of the previous frame, except for keyframes which start from zero, so a receiver
+//! that missed a frame resumes at the next keyframe.
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::telemetry::{Channels, TelemetryFrame, MAX_FRAME_SAMPLES};
+use crate::units::{Bar, Celsius, MillilitersPerSecond};
+
+const CHANNEL_COUNT: usize = 5;
+
+/// Largest encoding of one sample: a varint offset and a varint per channel.
+const MAX_SAMPLE_LEN: usize = 5 * (1 + CHANNEL_COUNT);
+
+pub const MAX_COMPACT_LEN: usize = MAX_FRAME_SAMPLES * MAX_SAMPLE_LEN;
+
+/// Frames sent between keyframes by default.
+pub const DEFAULT_KEYFRAME_INTERVAL: u16 = 10;
+
+/// Fixed point value standing in for `NaN`.
+const NAN: i32 = i32::MIN;
+
+/// Steps per unit of each channel, in [Channels] order.
+pub struct Resolution;
+
+impl Resolution {
+    /// 0.01 °C
+    pub const BOILER: f32 = 100.0;
+    /// 1 mbar
+    pub const PRESSURE: f32 = 1000.0;
+    /// 0.01 ml/s
+    pub const FLOW: f32 = 100.0;
+    /// 0.1 %
+    pub const DUTY: f32 = 10.0;
+
+    const ALL: [f32; CHANNEL_COUNT] = [Self::BOILER, Self::PRESSURE, Self::FLOW, Self::DUTY, Self::DUTY];
+}
+
+/// A [TelemetryFrame] in compact form.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct CompactFrame {
+    /// Counts up by one for every frame, so the receiver notices a missing one.
+    pub sequence: u16,
+    /// The first sample is stored as is rather than relative to the previous frame.
+    pub keyframe: bool,
+    /// Microseconds since the host (MCU) booted, at the first sample.
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub base_timestamp_us: u64,
+    pub data: Vec<u8, MAX_COMPACT_LEN>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DeltaError {
+    /// A frame before this one was missed, and this one is not a keyframe.
+    MissingKeyframe,
+    /// The data ended inside a sample, or holds more samples than a frame can.
+    Malformed,
+}
+
+type Fixed = [i32; CHANNEL_COUNT];
+
+fn to_fixed(channels: &Channels) -> Fixed {
+    let values = [
+        channels.boiler.0,
+        channels.pressure.0,
+        channels.flow.0,
+        channels.pump_duty,
+        channels.heater_duty,
+    ];
+
+    let mut fixed = [0; CHANNEL_COUNT];
+    for ((fixed, value), resolution) in fixed.iter_mut().zip(values).zip(Resolution::ALL) {
+        *fixed = quantize(value, resolution);
+    }
+    fixed
+}
+
+fn from_fixed(fixed: &Fixed) -> Channels {
+    let mut values = [0.0; CHANNEL_COUNT];
+    for ((value, fixed), resolution) in values.iter_mut().zip(fixed).zip(Resolution::ALL) {
+        *value = if *fixed == NAN { f32::NAN } else { *fixed as f32 / resolution };
+    }
+
+    Channels {
+        boiler: Celsius(values[0]),
+        pressure: Bar(values[1]),
+        flow: MillilitersPerSecond(values[2]),
+        pump_duty: values[3],
+        heater_duty: values[4],
+    }
+}
+
+/// Rounds to the nearest step, saturating out of range values.
+fn quantize(value: f32, resolution: f32) -> i32 {
+    if value.is_nan() {
+        return NAN;
+    }
+
+    let scaled = value * resolution;
+    let rounded = if scaled >= 0.0 { scaled + 0.5 } else { scaled - 0.5 };
+    (rounded as i32).max(NAN + 1)
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+fn unzigzag(value: u32) -> i32 {
+    ((value >> 1) as i32) ^ -((value & 1) as i32)
+}
+
+fn push_varint<const N: usize>(out: &mut Vec<u8, N>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        // Capacity is reserved per sample by MAX_SAMPLE_LEN
+        if value == 0 {
+            let _ = out.push(byte);
+            return;
+        }
+        let _ = out.push(byte | 0x80);
+    }
+}
+
+fn take_varint(input: &mut &[u8]) -> Option<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let (&byte, rest) = input.split_first()?;
+        *input = rest;
+        value |= ((byte & 0x7f) as u32).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Turns [TelemetryFrame]s into [CompactFrame]s on the host (MCU).
+pub struct DeltaEncoder {
+    keyframe_interval: u16,
+    since_keyframe: u16,
+    sequence: u16,
+    previous: Option<Fixed>,
+}
+
+impl DeltaEncoder {
+    /// Sends a keyframe every `keyframe_interval` frames, at least 1.
+    pub const fn new(keyframe_interval: u16) -> Self {
+        Self {
+            keyframe_interval,
+            since_keyframe: 0,
+            sequence: 0,
+            previous: None,
+        }
+    }
+
+    /// Makes the next frame a keyframe, e.g. after a frame could not be sent.
+    pub fn force_keyframe(&mut self) {
+        self.previous = None;
+    }
+
+    pub fn encode(&mut self, frame: &TelemetryFrame) -> CompactFrame {
+        if self.since_keyframe >= self.keyframe_interval {
+            self.previous = None;
+        }
+        let keyframe = self.previous.is_none();
+        self.since_keyframe = if keyframe { 1 } else { self.since_keyframe + 1 };
+
+        let mut data = Vec::new();
+        let mut previous = self.previous.unwrap_or_default();
+        let mut previous_offset = 0;
+        for sample in &frame.samples {
+            push_varint(&mut data, zigzag(sample.offset_us.wrapping_sub(previous_offset) as i32));
+            previous_offset = sample.offset_us;
+
+            let fixed = to_fixed(&sample.channels);
+            for (value, previous) in fixed.iter().zip(previous) {
+                push_varint(&mut data, zigzag(value.wrapping_sub(previous)));
+            }
+            previous = fixed;
+        }
+        if !frame.samples.is_empty() {
+            self.previous = Some(previous);
+        }
+
+        let compact = CompactFrame {
+            sequence: self.sequence,
+            keyframe,
+            base_timestamp_us: frame.base_timestamp_us,
+            data,
+        };
+        self.sequence = self.sequence.wrapping_add(1);
+        compact
+    }
+}
+
+impl Default for DeltaEncoder {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEYFRAME_INTERVAL)
+    }
+}
+
+/// Turns [CompactFrame]s back into [TelemetryFrame]s on the client.
+///
+/// ```
+/// use axis_protocol::delta::{DeltaDecoder, DeltaEncoder, DeltaError};
+/// use axis_protocol::telemetry::{Channels, TelemetryFrame};
+/// use axis_protocol::units::{Bar, Celsius, MillilitersPerSecond};
+///
+/// let mut encoder = DeltaEncoder::new(3);
+/// let mut decoder = DeltaDecoder::new();
+///
+/// let mut frames = Vec::new();
+/// for f in 0..6u64 {
+///     let mut frame = TelemetryFrame::new();
+///     for s in 0..8u64 {
+///         let t = f * 80 + s * 10;
+///         let channels = Channels {
+///             boiler: Celsius(92.0 + t as f32 * 0.01),
+///             pressure: Bar(9.0 - t as f32 * 0.001),
+///             flow: MillilitersPerSecond(-1.5),
+///             pump_duty: 80.0,
+///             heater_duty: f32::NAN,
+///         };
+///         frame.push(t * 1000, channels).unwrap();
+///     }
+///     frames.push((frame.clone(), encoder.encode(&frame)));
+/// }
+///
+/// // Every frame decodes to its samples, within the resolution of each channel
+/// for (frame, compact) in &frames {
+///     let decoded = decoder.decode(compact).unwrap();
+///     for ((t, a), (u, b)) in frame.iter().zip(decoded.iter()) {
+///         assert_eq!(t, u);
+///         assert!((a.boiler.0 - b.boiler.0).abs() <= 0.005);
+///         assert!((a.pressure.0 - b.pressure.0).abs() <= 0.0005);
+///         assert_eq!(a.flow, b.flow);
+///         assert_eq!(a.pump_duty, b.pump_duty);
+///         assert!(b.heater_duty.is_nan());
+///     }
+/// }
+///
+/// // After a missed frame, decoding resumes at the next keyframe
+/// let mut decoder = DeltaDecoder::new();
+/// decoder.decode(&frames[0].1).unwrap();
+/// assert_eq!(decoder.decode(&frames[2].1), Err(DeltaError::MissingKeyframe));
+/// assert!(frames[3].1.keyframe);
+/// decoder.decode(&frames[3].1).unwrap();
+/// decoder.decode(&frames[4].1).unwrap();
+/// ```
+pub struct DeltaDecoder {
+    expected_sequence: Option<u16>,
+    previous: Option<Fixed>,
+}
+
+impl DeltaDecoder {
+    pub const fn new() -> Self {
+        Self {
+            expected_sequence: None,
+            previous: None,
+        }
+    }
+
+    pub fn decode(&mut self, compact: &CompactFrame) -> Result<TelemetryFrame, DeltaError> {
+        let in_sequence = self.expected_sequence == Some(compact.sequence);
+        self.expected_sequence = Some(compact.sequence.wrapping_add(1));
+
+        let mut previous = match (compact.keyframe, self.previous) {
+            (true, _) => Fixed::default(),
+            (false, Some(previous)) if in_sequence => previous,
+            _ => {
+                self.previous = None;
+                return Err(DeltaError::MissingKeyframe);
+            }
+        };
+
+        let mut frame = TelemetryFrame::new();
+        frame.base_timestamp_us = compact.base_timestamp_us;
+
+        let mut input = compact.data.as_slice();
+        let mut offset = 0u32;
+        while !input.is_empty() {
+            let sample = self.decode_sample(&mut input, &mut offset, &mut previous);
+            let Some(channels) = sample else {
+                self.previous = None;
+                return Err(DeltaError::Malformed);
+            };
+            let timestamp = compact.base_timestamp_us.checked_add(offset as u64);
+            let pushed = timestamp.and_then(|timestamp| frame.push(timestamp, channels).ok());
+            if pushed.is_none() {
+                self.previous = None;
+                return Err(DeltaError::Malformed);
+            }
+        }
+
+        if !frame.is_empty() {
+            self.previous = Some(previous);
+        }
+        Ok(frame)
+    }
+
+    fn decode_sample(&self, input: &mut &[u8], offset: &mut u32, previous: &mut Fixed) -> Option<Channels> {
+        *offset = offset.wrapping_add(unzigzag(take_varint(input)?) as u32);
+        for value in previous.iter_mut() {
+            *value = value.wrapping_add(unzigzag(take_varint(input)?));
+        }
+        Some(from_fixed(previous))
+    }
+}
+
+impl Default for DeltaDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels(value: f32) -> Channels {
+        Channels {
+            boiler: Celsius(value),
+            pressure: Bar(value),
+            flow: MillilitersPerSecond(value),
+            pump_duty: value,
+            heater_duty: value,
+        }
+    }
+
+    fn values(channels: &Channels) -> [f32; CHANNEL_COUNT] {
+        [
+            channels.boiler.0,
+            channels.pressure.0,
+            channels.flow.0,
+            channels.pump_duty,
+            channels.heater_duty,
+        ]
+    }
+
+    fn round_trip(frame: &TelemetryFrame) -> TelemetryFrame {
+        DeltaDecoder::new().decode(&DeltaEncoder::default().encode(frame)).unwrap()
+    }
+
+    #[test]
+    fn nan_round_trips_next_to_numbers() {
+        let mut frame = TelemetryFrame::new();
+        for (t, value) in [1.5, f32::NAN, -2.5, f32::NAN].into_iter().enumerate() {
+            frame.push(t as u64 * 10, channels(value)).unwrap();
+        }
+
+        let decoded = round_trip(&frame);
+        let mut decoded = decoded.iter().map(|(_, channels)| values(channels));
+        assert_eq!(decoded.next(), Some([1.5; CHANNEL_COUNT]));
+        assert!(decoded.next().unwrap().iter().all(|v| v.is_nan()));
+        assert_eq!(decoded.next(), Some([-2.5; CHANNEL_COUNT]));
+        assert!(decoded.next().unwrap().iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn out_of_range_values_saturate() {
+        let mut frame = TelemetryFrame::new();
+        for (t, value) in [f32::INFINITY, f32::NEG_INFINITY, 1e30, -1e30].into_iter().enumerate() {
+            frame.push(t as u64, channels(value)).unwrap();
+        }
+
+        let decoded = round_trip(&frame);
+        for ((_, channels), positive) in decoded.iter().zip([true, false, true, false]) {
+            for (value, resolution) in values(channels).into_iter().zip(Resolution::ALL) {
+                let limit = if positive { i32::MAX } else { NAN + 1 };
+                assert_eq!(value, limit as f32 / resolution);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_frames_round_trip_and_keep_the_previous_samples() {
+        let mut encoder = DeltaEncoder::default();
+        let mut decoder = DeltaDecoder::new();
+
+        let mut frame = TelemetryFrame::new();
+        frame.push(100, channels(3.0)).unwrap();
+        decoder.decode(&encoder.encode(&frame)).unwrap();
+
+        let empty = encoder.encode(&TelemetryFrame::new());
+        assert!(empty.data.is_empty());
+        assert!(decoder.decode(&empty).unwrap().is_empty());
+
+        // The next frame still continues from the last sample before the empty one
+        let mut frame = TelemetryFrame::new();
+        frame.push(200, channels(4.0)).unwrap();
+        let next = encoder.encode(&frame);
+        assert!(!next.keyframe);
+        assert_eq!(values(decoder.decode(&next).unwrap().iter().next().unwrap().1)[0], 4.0);
+    }
+
+    #[test]
+    fn truncated_data_is_malformed() {
+        let mut frame = TelemetryFrame::new();
+        frame.push(0, channels(93.0)).unwrap();
+        let mut compact = DeltaEncoder::default().encode(&frame);
+
+        compact.data.pop();
+        assert_eq!(DeltaDecoder::new().decode(&compact), Err(DeltaError::Malformed));
+
+        // A varint that never ends
+        compact.data = Vec::from_slice(&[0x80; 6]).unwrap();
+        assert_eq!(DeltaDecoder::new().decode(&compact), Err(DeltaError::Malformed));
+    }
+
+    #[test]
+    fn overlong_data_is_malformed() {
+        let mut compact = DeltaEncoder::default().encode(&TelemetryFrame::new());
+        // One sample more than a frame holds, each repeating the previous one
+        for _ in 0..(MAX_FRAME_SAMPLES + 1) * (1 + CHANNEL_COUNT) {
+            compact.data.push(0).unwrap();
+        }
+        assert_eq!(DeltaDecoder::new().decode(&compact), Err(DeltaError::Malformed));
+    }
+
+    #[test]
+    fn timestamps_past_the_end_of_time_are_malformed() {
+        let mut compact = DeltaEncoder::default().encode(&TelemetryFrame::new());
+        compact.base_timestamp_us = u64::MAX;
+        // An offset of 1, then all channels unchanged
+        compact.data = Vec::from_slice(&[2, 0, 0, 0, 0, 0]).unwrap();
+        assert_eq!(DeltaDecoder::new().decode(&compact), Err(DeltaError::Malformed));
+    }
+}
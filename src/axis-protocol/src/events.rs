@@ -1,3 +1,115 @@
 use serde::{Deserialize, Serialize};
 
+use crate::units::Celsius;
 
+/// Something noteworthy that happened on the host (MCU), pushed to the client as
+/// [Messages::Event](crate::messages::Messages::Event)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct Event {
+    /// Stable identifier of the event kind, see [EventKind::code].
+    pub code: u16,
+    pub severity: Severity,
+    /// Microseconds since the host (MCU) booted.
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub timestamp_us: u64,
+    pub kind: EventKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum Severity {
+    Debug = 0,
+    Info = 1,
+    Warning = 2,
+    Error = 3,
+    Critical = 4,
+}
+
+/// Codes are grouped by subsystem in blocks of 100 and must never be reused once assigned.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum EventKind {
+    ThermocoupleFault(ThermocoupleFault) = 0,
+    I2cFailure { device: I2cDevice } = 1,
+    OverTemperature { temperature: Celsius, limit: Celsius } = 2,
+    ShotStarted = 3,
+    ShotStopped { duration_ms: u32 } = 4,
+    ModeChanged { from: Mode, to: Mode } = 5,
+}
+
+/// Fault bits reported by a MAX31855 thermocouple converter
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct ThermocoupleFault {
+    pub short_to_vcc: bool,
+    pub short_to_gnd: bool,
+    pub open_circuit: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum I2cDevice {
+    Ads1119 = 0,
+    Ds3231m = 1,
+    Fm24cl16b = 2,
+    Pca9544a = 3,
+    Tca9534 = 4,
+}
+
+/// Operating mode of the machine
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum Mode {
+    Standby = 0,
+    Heating = 1,
+    Ready = 2,
+    Brewing = 3,
+    Steaming = 4,
+    Fault = 5,
+}
+
+impl Event {
+    pub fn new(timestamp_us: u64, kind: EventKind) -> Self {
+        Self {
+            code: kind.code(),
+            severity: kind.severity(),
+            timestamp_us,
+            kind,
+        }
+    }
+}
+
+impl EventKind {
+    pub const fn code(&self) -> u16 {
+        match self {
+            EventKind::ThermocoupleFault(_) => 100,
+            EventKind::I2cFailure { .. } => 101,
+            EventKind::OverTemperature { .. } => 200,
+            EventKind::ShotStarted => 300,
+            EventKind::ShotStopped { .. } => 301,
+            EventKind::ModeChanged { .. } => 400,
+        }
+    }
+
+    pub const fn severity(&self) -> Severity {
+        match self {
+            EventKind::ThermocoupleFault(_) => Severity::Error,
+            EventKind::I2cFailure { .. } => Severity::Error,
+            EventKind::OverTemperature { .. } => Severity::Critical,
+            EventKind::ShotStarted => Severity::Info,
+            EventKind::ShotStopped { .. } => Severity::Info,
+            EventKind::ModeChanged { .. } => Severity::Info,
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Splits packets too large for a single frame into numbered fragments, and reassembles them on
+//! the receiving side.
+//!
+//! Each fragment is a packet of its own: a [MessageHeader] with [MessageId::Fragment] whose
+//! sequence number identifies the transfer, a [FragmentHeader], then up to
+//! [FRAGMENT_DATA_LEN] bytes of the original packet. Fragments must arrive in order.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{MessageHeader, MessageId};
+
+/// Bytes of the original packet carried by each fragment, chosen so a framed fragment fits in a
+/// single 64 byte USB packet.
+pub const FRAGMENT_DATA_LEN: usize = 48;
+
+/// Largest fragment packet, headers included.
+pub const MAX_FRAGMENT_LEN: usize = 3 + 3 + 3 + FRAGMENT_DATA_LEN;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FragmentHeader {
+    pub index: u16,
+    pub count: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FragmentError {
+    /// The packet needs more fragments than a transfer can hold, or more space than the
+    /// reassembly buffer has.
+    TooLarge,
+    /// The reassembled packet does not fit into the reassembly buffer.
+    Overflow,
+    /// The output buffer cannot hold a fragment.
+    BufferTooSmall,
+    /// The fragment header could not be deserialized, or is inconsistent.
+    Malformed,
+    /// A transfer was abandoned before all of its fragments arrived.
+    Incomplete { transfer: u8, received: u16, count: u16 },
+}
+
+/// Whether a packet of `len` bytes has to be sent as fragments.
+pub const fn needs_fragmenting(len: usize) -> bool {
+    len > MAX_FRAGMENT_LEN
+}
+
+/// Produces the fragments of a single packet.
+///
+/// ```
+/// use axis_protocol::fragment::{Fragments, Reassembler, MAX_FRAGMENT_LEN};
+///
+/// let packet = [7u8; 200];
+/// let mut fragments = Fragments::new(&packet, 1).unwrap();
+/// let mut reassembler = Reassembler::<256>::new();
+///
+/// let mut buf = [0u8; MAX_FRAGMENT_LEN];
+/// let mut reassembled = None;
+/// while let Some(fragment) = fragments.next_into(&mut buf) {
+///     let (header, body) = axis_protocol::decode_header(fragment.unwrap()).unwrap();
+///     if let Some(done) = reassembler.push(header, body).unwrap() {
+///         reassembled = Some(done.len());
+///     }
+/// }
+/// assert_eq!(reassembled, Some(200));
+/// ```
+pub struct Fragments<'a> {
+    packet: &'a [u8],
+    transfer: u8,
+    index: u16,
+    count: u16,
+}
+
+impl<'a> Fragments<'a> {
+    /// Fragments `packet` as transfer `transfer`, which should differ from the previous one.
+    pub fn new(packet: &'a [u8], transfer: u8) -> Result<Self, FragmentError> {
+        let count = packet.len().div_ceil(FRAGMENT_DATA_LEN).max(1);
+        let count = u16::try_from(count).map_err(|_| FragmentError::TooLarge)?;
+
+        Ok(Self {
+            packet,
+            transfer,
+            index: 0,
+            count,
+        })
+    }
+
+    pub fn count(&self) -> u16 {
+        self.count
+    }
+
+    /// Serializes the next fragment into `out`, or returns `None` once all have been produced.
+    pub fn next_into<'b>(&mut self, out: &'b mut [u8]) -> Option<Result<&'b [u8], FragmentError>> {
+        if self.index >= self.count {
+            return None;
+        }
+
+        let start = self.index as usize * FRAGMENT_DATA_LEN;
+        let end = (start + FRAGMENT_DATA_LEN).min(self.packet.len());
+        let data = &self.packet[start..end];
+
+        let header = MessageHeader::new(self.transfer, MessageId::Fragment);
+        let fragment_header = FragmentHeader {
+            index: self.index,
+            count: self.count,
+        };
+        self.index += 1;
+
+        let Ok(len) = crate::encode_packet(header, &fragment_header, out).map(|p| p.len()) else {
+            return Some(Err(FragmentError::BufferTooSmall));
+        };
+        let Some(dest) = out.get_mut(len..len + data.len()) else {
+            return Some(Err(FragmentError::BufferTooSmall));
+        };
+        dest.copy_from_slice(data);
+
+        Some(Ok(&out[..len + data.len()]))
+    }
+}
+
+/// Collects the fragments of one transfer at a time into a buffer of `N` bytes.
+pub struct Reassembler<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    transfer: u8,
+    received: u16,
+    count: u16,
+    /// Set once the current transfer has been rejected, so its remaining fragments are dropped
+    /// without reporting an error for each.
+    dropping: bool,
+}
+
+impl<const N: usize> Reassembler<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0u8; N],
+            len: 0,
+            transfer: 0,
+            received: 0,
+            count: 0,
+            dropping: false,
+        }
+    }
+
+    /// Whether a transfer has been started but not completed.
+    pub fn in_progress(&self) -> bool {
+        self.received > 0
+    }
+
+    /// Abandons the current transfer, reporting it as [FragmentError::Incomplete] if one was in
+    /// progress, e.g. when the link drops or no fragment arrived for too long.
+    pub fn abort(&mut self) -> Result<(), FragmentError> {
+        let result = self.incomplete();
+        self.reset();
+        result
+    }
+
+    /// Adds a fragment, given its header and the rest of its packet. Returns the reassembled
+    /// packet once the last fragment of a transfer arrives.
+    ///
+    /// A first fragment always starts a new transfer. If another transfer was still in progress
+    /// it is dropped and reported as [FragmentError::Incomplete], while the new transfer
+    /// continues with the next fragment.
+    pub fn push(&mut self, header: MessageHeader, body: &[u8]) -> Result<Option<&[u8]>, FragmentError> {
+        let Ok((fragment, data)) = postcard::take_from_bytes::<FragmentHeader>(body) else {
+            self.reset();
+            return Err(FragmentError::Malformed);
+        };
+
+        if fragment.count == 0 || fragment.index >= fragment.count {
+            self.reset();
+            return Err(FragmentError::Malformed);
+        }
+
+        let transfer = header.get_sequence();
+
+        let mut abandoned = Ok(());
+        if fragment.index == 0 {
+            abandoned = self.incomplete();
+            self.reset();
+            self.transfer = transfer;
+            self.count = fragment.count;
+
+            let min_len = (fragment.count as usize - 1) * FRAGMENT_DATA_LEN + 1;
+            if min_len > N {
+                self.dropping = true;
+                return Err(FragmentError::TooLarge);
+            }
+        } else if self.dropping && transfer == self.transfer {
+            return Ok(None);
+        } else if !self.in_progress()
+            || transfer != self.transfer
+            || fragment.count != self.count
+            || fragment.index != self.received
+        {
+            let abandoned = self.incomplete();
+            self.reset();
+            abandoned?;
+            return Err(FragmentError::Malformed);
+        }
+
+        let Some(dest) = self.buf.get_mut(self.len..self.len + data.len()) else {
+            self.reset();
+            self.dropping = true;
+            return Err(FragmentError::Overflow);
+        };
+        dest.copy_from_slice(data);
+        self.len += data.len();
+        self.received += 1;
+
+        if self.received < self.count {
+            abandoned?;
+            return Ok(None);
+        }
+
+        let len = self.len;
+        self.reset();
+        Ok(Some(&self.buf[..len]))
+    }
+
+    fn incomplete(&self) -> Result<(), FragmentError> {
+        if self.in_progress() {
+            Err(FragmentError::Incomplete {
+                transfer: self.transfer,
+                received: self.received,
+                count: self.count,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn reset(&mut self) {
+        self.len = 0;
+        self.received = 0;
+        self.count = 0;
+        self.dropping = false;
+    }
+}
+
+impl<const N: usize> Default for Reassembler<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
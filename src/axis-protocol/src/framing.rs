@@ -0,0 +1,162 @@
+//! Delimits packets on a byte stream (e.g. USB CDC) so they survive writes being coalesced or
+//! split by the host.
+//!
+//! A frame is the packet followed by its CRC-16 (little endian), COBS encoded and terminated
+//! with a single `0x00` [FRAME_DELIMITER].
+
+use crc::{Crc, CRC_16_IBM_SDLC};
+
+pub const FRAME_DELIMITER: u8 = 0x00;
+
+const CRC: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_SDLC);
+const CRC_LEN: usize = 2;
+
+/// Largest encoded frame, delimiter included, for a packet of `packet_len` bytes.
+pub const fn max_frame_len(packet_len: usize) -> usize {
+    let raw_len = packet_len + CRC_LEN;
+    raw_len + raw_len / 254 + 1 + 1
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameError {
+    /// The output buffer cannot hold the encoded frame.
+    BufferTooSmall,
+    /// The frame did not fit into the decoder's buffer and was dropped.
+    Overflow,
+    /// The frame is not valid COBS.
+    Cobs,
+    /// The frame is too short to contain a CRC.
+    Truncated,
+    /// The CRC does not match the packet.
+    Crc,
+}
+
+/// Encodes `packet` into `out` as a single frame, returning the number of bytes written.
+pub fn encode_frame(packet: &[u8], out: &mut [u8]) -> Result<usize, FrameError> {
+    if out.len() < max_frame_len(packet.len()) {
+        return Err(FrameError::BufferTooSmall);
+    }
+
+    let crc = CRC.checksum(packet).to_le_bytes();
+
+    let mut encoder = cobs::CobsEncoder::new(out);
+    encoder.push(packet).map_err(|_| FrameError::BufferTooSmall)?;
+    encoder.push(&crc).map_err(|_| FrameError::BufferTooSmall)?;
+    let len = encoder.finalize().map_err(|_| FrameError::BufferTooSmall)?;
+
+    out[len] = FRAME_DELIMITER;
+    Ok(len + 1)
+}
+
+/// Result of feeding bytes into a [FrameDecoder]
+pub enum FeedResult<'a, 'b> {
+    /// All input was consumed without completing a frame.
+    Consumed,
+    /// A frame was completed. `remaining` holds the input after its delimiter.
+    Frame { packet: &'a [u8], remaining: &'b [u8] },
+    /// A frame was completed but had to be dropped. `remaining` holds the input after its
+    /// delimiter.
+    Error { error: FrameError, remaining: &'b [u8] },
+}
+
+/// Accumulates stream bytes and yields complete, CRC checked packets.
+///
+/// Corrupted, oversized or partial frames are dropped, and decoding resumes at the next
+/// [FRAME_DELIMITER].
+///
+/// ```
+/// use axis_protocol::framing::{encode_frame, FeedResult, FrameDecoder};
+///
+/// let mut frame = [0u8; 16];
+/// let len = encode_frame(&[1, 0, 2], &mut frame).unwrap();
+///
+/// let mut decoder = FrameDecoder::<16>::new();
+/// match decoder.feed(&frame[..len]) {
+///     FeedResult::Frame { packet, remaining } => {
+///         assert_eq!(packet, &[1, 0, 2]);
+///         assert!(remaining.is_empty());
+///     }
+///     _ => panic!("expected a frame"),
+/// }
+/// ```
+pub struct FrameDecoder<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    overflowed: bool,
+}
+
+impl<const N: usize> FrameDecoder<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0u8; N],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Drops any partially received frame.
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.overflowed = false;
+    }
+
+    /// Feeds `input` until a frame is completed or the input runs out. Call again with
+    /// `remaining` to continue after a [FeedResult::Frame] or [FeedResult::Error].
+    pub fn feed<'a, 'b>(&'a mut self, input: &'b [u8]) -> FeedResult<'a, 'b> {
+        let Some(end) = input.iter().position(|b| *b == FRAME_DELIMITER) else {
+            self.push(input);
+            return FeedResult::Consumed;
+        };
+
+        self.push(&input[..end]);
+        let remaining = &input[end + 1..];
+
+        let overflowed = self.overflowed;
+        let len = self.len;
+        self.reset();
+
+        if overflowed {
+            return FeedResult::Error { error: FrameError::Overflow, remaining };
+        }
+
+        match Self::decode(&mut self.buf[..len]) {
+            Ok(packet) => FeedResult::Frame { packet, remaining },
+            Err(error) => FeedResult::Error { error, remaining },
+        }
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        if self.overflowed {
+            return;
+        }
+
+        match self.buf.get_mut(self.len..self.len + data.len()) {
+            Some(dest) => {
+                dest.copy_from_slice(data);
+                self.len += data.len();
+            }
+            None => self.overflowed = true,
+        }
+    }
+
+    fn decode(raw: &mut [u8]) -> Result<&[u8], FrameError> {
+        let len = cobs::decode_in_place(raw).map_err(|_| FrameError::Cobs)?;
+        if len < CRC_LEN {
+            return Err(FrameError::Truncated);
+        }
+
+        let (packet, crc) = raw[..len].split_at(len - CRC_LEN);
+        if CRC.checksum(packet).to_le_bytes() != crc {
+            return Err(FrameError::Crc);
+        }
+
+        Ok(packet)
+    }
+}
+
+impl<const N: usize> Default for FrameDecoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,239 @@
+//! Hello exchange run by the client before any other traffic, so both sides can agree they speak
+//! the same protocol and the client learns what it is talking to.
+//!
+//! The client sends [Commands::Hello](crate::messages::Commands::Hello) with its
+//! [PROTOCOL_VERSION], and the host (MCU) answers with
+//! [Responses::Hello](crate::messages::Responses::Hello) describing itself. Until the exchange
+//! completes, the host (MCU) rejects every other command.
+
+use core::fmt;
+use core::str;
+
+use serde::de::{self, Unexpected, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Version of the protocol implemented by this crate. Bump `major` for any change that alters
+/// how existing messages serialize, and `minor` when only adding messages or variants.
+pub const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion { major: 1, minor: 0 };
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct ProtocolVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct FirmwareVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum BoardRevision {
+    Unknown = 0,
+    V2 = 1,
+    V2Hv = 2,
+    McuV3 = 3,
+    McuV4 = 4,
+    BaseRev01 = 5,
+    GcpHat = 6,
+}
+
+/// Sensors and actuators fitted to a board, and optional features its firmware implements.
+/// Clients should not offer what is missing, the host (MCU) answers such commands with
+/// [CommandError::Unsupported](crate::messages::CommandError::Unsupported).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct Capabilities(pub u32);
+
+impl Capabilities {
+    pub const BOILER_THERMOCOUPLE: Self = Self(1 << 0);
+    pub const PRESSURE_SENSOR: Self = Self(1 << 1);
+    pub const FLOW_METER: Self = Self(1 << 2);
+    pub const SCALE: Self = Self(1 << 3);
+    pub const REAL_TIME_CLOCK: Self = Self(1 << 4);
+    pub const FRAM: Self = Self(1 << 5);
+    pub const HEATER: Self = Self(1 << 6);
+    pub const PUMP: Self = Self(1 << 7);
+    pub const SOLENOID: Self = Self(1 << 8);
+    /// Accepts [Commands::BeginUpdate](crate::messages::Commands::BeginUpdate) and the rest of
+    /// the firmware update commands.
+    pub const FIRMWARE_UPDATE: Self = Self(1 << 9);
+    /// Records shots and accepts [Commands::ListShots](crate::messages::Commands::ListShots) and
+    /// the rest of the shot history commands.
+    pub const SHOT_HISTORY: Self = Self(1 << 10);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// Identifies one board, taken from the unique ID of its flash chip. The USB serial number is
+/// the same ID written as [hex](Self::hex), so a client can match a port to the machine behind
+/// it before opening it.
+///
+/// Human readable formats like JSON get the hex string as well, so the desktop can key
+/// profiles and history by it, while postcard sends the 8 bytes.
+///
+/// ```
+/// use axis_protocol::handshake::SerialNumber;
+///
+/// let serial = SerialNumber([0xe6, 0x61, 0x38, 0x52, 0x83, 0x5f, 0x2a, 0x2f]);
+/// assert_eq!(serial.hex(&mut [0u8; 16]), "E6613852835F2A2F");
+/// assert_eq!(SerialNumber::parse("e6613852835f2a2f"), Some(serial));
+/// assert_eq!(SerialNumber::parse("12345678"), None);
+///
+/// assert_eq!(serde_json::to_string(&serial).unwrap(), r#""E6613852835F2A2F""#);
+/// assert_eq!(serde_json::from_str::<SerialNumber>(r#""e6613852835f2a2f""#).unwrap(), serial);
+/// let mut buf = [0u8; 8];
+/// assert_eq!(postcard::to_slice(&serial, &mut buf).unwrap(), &serial.0);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct SerialNumber(#[cfg_attr(feature = "ts", ts(type = "string"))] pub [u8; 8]);
+
+impl SerialNumber {
+    /// Length of the ID written as hex.
+    pub const HEX_LEN: usize = 16;
+
+    /// Writes the ID as upper case hex into `buf`.
+    pub fn hex<'a>(&self, buf: &'a mut [u8; Self::HEX_LEN]) -> &'a str {
+        const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+        for (i, byte) in self.0.iter().enumerate() {
+            buf[2 * i] = DIGITS[usize::from(byte >> 4)];
+            buf[2 * i + 1] = DIGITS[usize::from(byte & 0x0f)];
+        }
+        // Only ASCII digits were written
+        str::from_utf8(buf).unwrap_or_default()
+    }
+
+    /// Reads an ID written as hex, in either case.
+    pub fn parse(hex: &str) -> Option<Self> {
+        let hex = hex.as_bytes();
+        if hex.len() != Self::HEX_LEN || !hex.iter().all(u8::is_ascii_hexdigit) {
+            return None;
+        }
+        let mut id = [0u8; 8];
+        for (byte, pair) in id.iter_mut().zip(hex.chunks_exact(2)) {
+            let pair = str::from_utf8(pair).ok()?;
+            *byte = u8::from_str_radix(pair, 16).ok()?;
+        }
+        Some(Self(id))
+    }
+}
+
+impl fmt::Display for SerialNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.hex(&mut [0u8; Self::HEX_LEN]))
+    }
+}
+
+impl Serialize for SerialNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(self.hex(&mut [0u8; Self::HEX_LEN]))
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SerialNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HexVisitor;
+
+        impl Visitor<'_> for HexVisitor {
+            type Value = SerialNumber;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{} hex digits", SerialNumber::HEX_LEN)
+            }
+
+            fn visit_str<E: de::Error>(self, hex: &str) -> Result<SerialNumber, E> {
+                SerialNumber::parse(hex).ok_or_else(|| E::invalid_value(Unexpected::Str(hex), &self))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(HexVisitor)
+        } else {
+            <[u8; 8]>::deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+/// Sent by the client to open a session.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct Hello {
+    pub protocol: ProtocolVersion,
+}
+
+/// The host (MCU)'s answer to [Hello]. New fields may only be appended, so older clients can
+/// still decode the fields they know.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct DeviceInfo {
+    pub protocol: ProtocolVersion,
+    pub firmware: FirmwareVersion,
+    pub board: BoardRevision,
+    pub capabilities: Capabilities,
+    pub serial: SerialNumber,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Compatibility {
+    /// Both sides implement the same protocol version.
+    Full,
+    /// Same major version, but one side knows messages the other does not. Unknown messages
+    /// will be rejected, everything else works.
+    Degraded,
+    /// Messages cannot be exchanged safely.
+    Incompatible,
+}
+
+impl Hello {
+    pub const fn new() -> Self {
+        Self { protocol: PROTOCOL_VERSION }
+    }
+}
+
+impl Default for Hello {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProtocolVersion {
+    /// How well a peer speaking `other` can talk to one speaking `self`.
+    pub fn compatibility(&self, other: &ProtocolVersion) -> Compatibility {
+        if self.major != other.major {
+            Compatibility::Incompatible
+        } else if self.minor != other.minor {
+            Compatibility::Degraded
+        } else {
+            Compatibility::Full
+        }
+    }
+}
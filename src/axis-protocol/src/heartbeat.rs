@@ -0,0 +1,27 @@
+//! Liveness signal sent periodically in both directions, as a [MessageId::Heartbeat] packet
+//! with a [Heartbeat] body. Heartbeats are fire-and-forget; a side that hears nothing from its
+//! peer for longer than the link timeout treats the link as lost.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{MessageHeader, MessageId};
+
+/// How often each side sends a heartbeat.
+pub const HEARTBEAT_INTERVAL_MS: u64 = 250;
+
+/// Default time without any packet from the peer after which the link is considered lost. The
+/// host (MCU) reads its timeout from [params::LINK_TIMEOUT](crate::params::LINK_TIMEOUT).
+pub const DEFAULT_LINK_TIMEOUT_MS: u32 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct Heartbeat {
+    /// Milliseconds since the sender started, wrapping.
+    pub uptime_ms: u32,
+}
+
+/// Serializes a heartbeat into `buf`.
+pub fn encode_heartbeat(sequence: u8, heartbeat: Heartbeat, buf: &mut [u8]) -> postcard::Result<&mut [u8]> {
+    crate::encode_packet(MessageHeader::new(sequence, MessageId::Heartbeat), &heartbeat, buf)
+}
@@ -0,0 +1,245 @@
+//! Shots recorded by the host (MCU), downloaded by the client in pages so each response fits
+//! into one message.
+//!
+//! 1. [Commands::ListShots](crate::messages::Commands::ListShots) pages through the recorded
+//!    shots in order of their [ShotId], see [ShotListing].
+//! 2. [Commands::ReadShot](crate::messages::Commands::ReadShot) fetches one shot's samples
+//!    starting at a sample offset, see [ShotDownload].
+//! 3. [Commands::AcknowledgeShot](crate::messages::Commands::AcknowledgeShot) marks a shot as
+//!    downloaded, and [Commands::DeleteShot](crate::messages::Commands::DeleteShot) frees its
+//!    storage.
+//!
+//! Every request names where to continue from, so a download interrupted by a lost link resumes
+//! with the next page instead of starting over.
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::messages::Commands;
+use crate::telemetry::Channels;
+use crate::units::Grams;
+
+/// Most shots listed in one [ShotPage].
+pub const MAX_SHOTS_PER_PAGE: usize = 8;
+/// Most samples carried by one [SamplePage].
+pub const MAX_SAMPLES_PER_PAGE: usize = 12;
+
+/// Identifies a recorded shot. Ids increase with every shot and are never reused.
+pub type ShotId = u32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct ShotSummary {
+    pub id: ShotId,
+    /// Seconds since the Unix epoch the shot started at, if the host (MCU) knew the time.
+    pub started_at: Option<u32>,
+    pub duration_ms: u32,
+    pub sample_count: u32,
+    /// Whether the shot was acknowledged by a client.
+    pub downloaded: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct ShotSample {
+    /// Milliseconds since the shot started.
+    pub offset_ms: u32,
+    pub channels: Channels,
+    /// Weight in the cup, `NaN` without a scale.
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub weight: Grams,
+}
+
+/// Answer to [Commands::ListShots](crate::messages::Commands::ListShots).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct ShotPage {
+    pub shots: Vec<ShotSummary, MAX_SHOTS_PER_PAGE>,
+    /// Whether more shots follow the last one in this page.
+    pub more: bool,
+}
+
+/// Answer to [Commands::ReadShot](crate::messages::Commands::ReadShot).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct SamplePage {
+    pub id: ShotId,
+    /// Index of the first sample in this page.
+    pub offset: u32,
+    /// Number of samples in the whole shot.
+    pub total: u32,
+    pub samples: Vec<ShotSample, MAX_SAMPLES_PER_PAGE>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum HistoryError {
+    /// No shot with this id is stored, or it was deleted.
+    UnknownShot = 0,
+    /// The offset is past the end of the shot, which has `total` samples.
+    OffsetOutOfRange { total: u32 } = 1,
+    /// Reading or writing the storage failed.
+    Storage = 2,
+}
+
+/// Pages through the recorded shots on the client.
+///
+/// ```
+/// use axis_protocol::history::{ShotListing, ShotPage, ShotSummary};
+/// use axis_protocol::messages::Commands;
+///
+/// let shot = |id| ShotSummary { id, started_at: None, duration_ms: 30_000, sample_count: 300, downloaded: false };
+/// let mut listing = ShotListing::new();
+///
+/// assert!(matches!(listing.request(), Some(Commands::ListShots { after: None })));
+/// let page = ShotPage { shots: heapless::Vec::from_slice(&[shot(3), shot(4)]).unwrap(), more: true };
+/// assert_eq!(listing.accept(&page).len(), 2);
+///
+/// assert!(matches!(listing.request(), Some(Commands::ListShots { after: Some(4) })));
+/// let page = ShotPage { shots: heapless::Vec::from_slice(&[shot(7)]).unwrap(), more: false };
+/// listing.accept(&page);
+/// assert!(listing.request().is_none());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShotListing {
+    after: Option<ShotId>,
+    done: bool,
+}
+
+impl ShotListing {
+    pub const fn new() -> Self {
+        Self { after: None, done: false }
+    }
+
+    /// Lists the shots recorded after `after`, e.g. the last one downloaded before.
+    pub const fn resume(after: ShotId) -> Self {
+        Self {
+            after: Some(after),
+            done: false,
+        }
+    }
+
+    /// The command fetching the next page, or `None` once every shot was listed.
+    pub fn request(&self) -> Option<Commands> {
+        (!self.done).then_some(Commands::ListShots { after: self.after })
+    }
+
+    /// Takes in the answer to the last [request](Self::request) and returns the shots it
+    /// listed. Shots at or before the ones already listed, e.g. from a repeated answer, are
+    /// skipped.
+    pub fn accept<'p>(&mut self, page: &'p ShotPage) -> &'p [ShotSummary] {
+        let start = match self.after {
+            Some(after) => page.shots.iter().position(|shot| shot.id > after).unwrap_or(page.shots.len()),
+            None => 0,
+        };
+        let shots = &page.shots[start..];
+
+        if let Some(last) = shots.last() {
+            self.after = Some(last.id);
+        }
+        // An empty page would request the same one forever
+        self.done = !page.more || shots.is_empty();
+        shots
+    }
+}
+
+/// Downloads the samples of one shot on the client, keeping track of where to resume.
+///
+/// ```
+/// use axis_protocol::history::{SamplePage, ShotDownload, ShotSample};
+/// use axis_protocol::messages::Commands;
+/// use axis_protocol::telemetry::Channels;
+/// use axis_protocol::units::{Bar, Celsius, Grams, MillilitersPerSecond};
+///
+/// let channels = Channels {
+///     boiler: Celsius(93.0),
+///     pressure: Bar(9.0),
+///     flow: MillilitersPerSecond(2.0),
+///     pump_duty: 80.0,
+///     heater_duty: 20.0,
+/// };
+/// let page = |offset: u32, len: u32| SamplePage {
+///     id: 12,
+///     offset,
+///     total: 20,
+///     samples: (offset..offset + len)
+///         .map(|i| ShotSample { offset_ms: i * 100, channels, weight: Grams(i as f32) })
+///         .collect(),
+/// };
+///
+/// let mut download = ShotDownload::new(12);
+/// assert!(matches!(download.request(), Some(Commands::ReadShot { id: 12, offset: 0 })));
+/// assert_eq!(download.accept(&page(0, 12)).unwrap().len(), 12);
+///
+/// // The link dropped, pick up from the saved offset
+/// let mut download = ShotDownload::resume(12, download.offset());
+/// assert!(matches!(download.request(), Some(Commands::ReadShot { id: 12, offset: 12 })));
+/// // A stale answer to an earlier request is ignored
+/// assert!(download.accept(&page(0, 12)).is_none());
+/// assert_eq!(download.accept(&page(12, 8)).unwrap().len(), 8);
+///
+/// assert!(download.is_complete());
+/// assert!(download.request().is_none());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShotDownload {
+    id: ShotId,
+    offset: u32,
+    total: Option<u32>,
+}
+
+impl ShotDownload {
+    pub const fn new(id: ShotId) -> Self {
+        Self::resume(id, 0)
+    }
+
+    /// Continues a download that already received the samples before `offset`.
+    pub const fn resume(id: ShotId, offset: u32) -> Self {
+        Self { id, offset, total: None }
+    }
+
+    pub fn id(&self) -> ShotId {
+        self.id
+    }
+
+    /// Number of samples received so far, the offset to resume from.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Number of samples in the shot, once the first page arrived.
+    pub fn total(&self) -> Option<u32> {
+        self.total
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.total.is_some_and(|total| self.offset >= total)
+    }
+
+    /// The command fetching the next page, or `None` once the download is complete.
+    pub fn request(&self) -> Option<Commands> {
+        (!self.is_complete()).then_some(Commands::ReadShot {
+            id: self.id,
+            offset: self.offset,
+        })
+    }
+
+    /// Takes in a page and returns its samples if it continues the download. Pages of another
+    /// shot or at another offset, e.g. repeated answers, give `None`.
+    pub fn accept<'p>(&mut self, page: &'p SamplePage) -> Option<&'p [ShotSample]> {
+        if page.id != self.id || page.offset != self.offset {
+            return None;
+        }
+
+        self.offset += page.samples.len() as u32;
+        // An empty page would request the same one forever
+        self.total = Some(if page.samples.is_empty() { self.offset } else { page.total });
+        Some(&page.samples)
+    }
+}
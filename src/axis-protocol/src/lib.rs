@@ -1,6 +1,25 @@
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
+pub mod auth;
+pub mod clock;
+#[cfg(feature = "std")]
+pub mod codec;
+pub mod decode;
+pub mod delta;
 pub mod events;
+pub mod fragment;
+pub mod framing;
+pub mod handshake;
+pub mod heartbeat;
+pub mod history;
+pub mod logs;
 pub mod messages;
+pub mod params;
+pub mod profile;
+pub mod reliability;
+pub mod telemetry;
+pub mod transport;
+pub mod units;
+pub mod update;
 
 use bitfield::bitfield;
 use serde::{Deserialize, Serialize};
@@ -12,3 +31,78 @@ bitfield! {
     pub get_sequence, set_sequence: 15, 8;
     pub get_message_id, set_message: 7, 0;
 }
+
+/// Identifies which type the body following a [MessageHeader] deserializes to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageId {
+    /// [messages::Messages], unsolicited from the host (MCU)
+    Message = 0,
+    /// [messages::Commands], from the client
+    Command = 1,
+    /// [messages::Response], from the host (MCU) in reply to a command
+    Response = 2,
+    /// [fragment::FragmentHeader] followed by part of a larger packet
+    Fragment = 3,
+    /// Sequence number of the reliable packet it acknowledges. The ack's own sequence number
+    /// counts up with the sender's other packets.
+    Ack = 4,
+    /// [heartbeat::Heartbeat], from either side
+    Heartbeat = 5,
+    /// Another packet followed by its [auth::Tag], in an authenticated session
+    Sealed = 6,
+}
+
+impl TryFrom<u8> for MessageId {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(MessageId::Message),
+            1 => Ok(MessageId::Command),
+            2 => Ok(MessageId::Response),
+            3 => Ok(MessageId::Fragment),
+            4 => Ok(MessageId::Ack),
+            5 => Ok(MessageId::Heartbeat),
+            6 => Ok(MessageId::Sealed),
+            other => Err(other),
+        }
+    }
+}
+
+impl MessageId {
+    /// Whether packets of this kind are acknowledged and retransmitted, see [reliability].
+    pub const fn is_reliable(self) -> bool {
+        matches!(self, MessageId::Command)
+    }
+}
+
+impl MessageHeader {
+    pub fn new(sequence: u8, message_id: MessageId) -> Self {
+        let mut header = MessageHeader(0);
+        header.set_sequence(sequence);
+        header.set_message(message_id as u8);
+        header
+    }
+
+    /// The [MessageId] of this header, or `None` if the id is unknown.
+    pub fn message_id(&self) -> Option<MessageId> {
+        MessageId::try_from(self.get_message_id()).ok()
+    }
+}
+
+/// Serializes `header` followed by `body` into `buf`, returning the written part of `buf`.
+pub fn encode_packet<'a, T: Serialize>(
+    header: MessageHeader,
+    body: &T,
+    buf: &'a mut [u8],
+) -> postcard::Result<&'a mut [u8]> {
+    let header_len = postcard::to_slice(&header, buf)?.len();
+    let body_len = postcard::to_slice(body, &mut buf[header_len..])?.len();
+    Ok(&mut buf[..header_len + body_len])
+}
+
+/// Splits a packet into its [MessageHeader] and the still serialized body.
+pub fn decode_header(packet: &[u8]) -> postcard::Result<(MessageHeader, &[u8])> {
+    postcard::take_from_bytes(packet)
+}
@@ -0,0 +1,176 @@
+//! Log records forwarded from the host (MCU) to the client, so device logs can be read over
+//! plain USB without a debug probe.
+//!
+//! Records are pushed as [Messages::Log](crate::messages::Messages::Log). The client picks the
+//! least severe level it wants with
+//! [Commands::SetLogLevel](crate::messages::Commands::SetLogLevel), and the host (MCU) limits
+//! how many records it sends with a [RateLimiter].
+
+use core::fmt::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+/// Longest module path sent with a record. Longer paths keep their last characters.
+pub const MAX_MODULE_LEN: usize = 32;
+/// Longest message sent with a record. Longer messages are cut off.
+pub const MAX_TEXT_LEN: usize = 96;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum LogLevel {
+    Trace = 0,
+    Debug = 1,
+    Info = 2,
+    Warn = 3,
+    Error = 4,
+}
+
+/// A single log line from the host (MCU).
+///
+/// ```
+/// use axis_protocol::logs::{LogLevel, LogRecord, MAX_TEXT_LEN};
+///
+/// let record = LogRecord::new(1_000, LogLevel::Warn, "axis_embedded::link", format_args!("lost {}", 3));
+/// assert_eq!(record.text, "lost 3");
+///
+/// let long = "x".repeat(200);
+/// let record = LogRecord::new(1_000, LogLevel::Info, "axis", format_args!("{long}"));
+/// assert!(record.truncated);
+/// assert_eq!(record.text.len(), MAX_TEXT_LEN);
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct LogRecord {
+    /// Microseconds since the host (MCU) booted.
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub timestamp_us: u64,
+    pub level: LogLevel,
+    #[cfg_attr(feature = "ts", ts(type = "string"))]
+    pub module: heapless::String<MAX_MODULE_LEN>,
+    #[cfg_attr(feature = "ts", ts(type = "string"))]
+    pub text: heapless::String<MAX_TEXT_LEN>,
+    /// Whether `text` was cut off at [MAX_TEXT_LEN].
+    pub truncated: bool,
+    /// Records dropped by the host (MCU) since the previous one was sent.
+    pub dropped: u16,
+}
+
+impl LogRecord {
+    /// Formats a record, cutting `module` and `text` down to size instead of failing.
+    pub fn new(timestamp_us: u64, level: LogLevel, module: &str, args: fmt::Arguments) -> Self {
+        let mut text = Truncating::<MAX_TEXT_LEN>::default();
+        // Truncating never fails, the text is only cut off.
+        let _ = text.write_fmt(args);
+
+        Self {
+            timestamp_us,
+            level,
+            module: module_tail(module),
+            text: text.buf,
+            truncated: text.truncated,
+            dropped: 0,
+        }
+    }
+}
+
+/// Writes as much as fits into a `heapless::String<N>`, remembering whether anything was cut off.
+#[derive(Default)]
+struct Truncating<const N: usize> {
+    buf: heapless::String<N>,
+    truncated: bool,
+}
+
+impl<const N: usize> Write for Truncating<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.buf.push(c).is_err() {
+                self.truncated = true;
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The last characters of `module`, as the end of a module path is the most specific part.
+fn module_tail<const N: usize>(module: &str) -> heapless::String<N> {
+    let start = module
+        .char_indices()
+        .map(|(i, _)| i)
+        .find(|&i| module.len() - i <= N)
+        .unwrap_or(module.len());
+
+    let mut tail = heapless::String::new();
+    let _ = tail.push_str(&module[start..]);
+    tail
+}
+
+/// Token bucket allowing bursts of `burst` records and `per_second` records on average. Times
+/// are milliseconds on any monotonic clock.
+///
+/// ```
+/// use axis_protocol::logs::RateLimiter;
+///
+/// let mut limiter = RateLimiter::new(2, 10);
+/// assert!(limiter.allow(0));
+/// assert!(limiter.allow(0));
+/// assert!(!limiter.allow(0));
+/// assert_eq!(limiter.take_dropped(), 1);
+///
+/// // One token comes back every 100 ms
+/// assert!(limiter.allow(100));
+/// assert!(!limiter.allow(150));
+/// ```
+pub struct RateLimiter {
+    burst: u32,
+    per_second: u32,
+    /// Available tokens, in thousandths so fractional refills are not lost.
+    milli_tokens: u32,
+    last_ms: u64,
+    dropped: u16,
+}
+
+impl RateLimiter {
+    pub const fn new(burst: u32, per_second: u32) -> Self {
+        Self {
+            burst,
+            per_second,
+            milli_tokens: burst * 1000,
+            last_ms: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Whether a record may be sent at `now_ms`. Refused records are counted, see
+    /// [take_dropped](Self::take_dropped).
+    pub fn allow(&mut self, now_ms: u64) -> bool {
+        let elapsed = now_ms.saturating_sub(self.last_ms);
+        self.last_ms = now_ms;
+
+        let refill = elapsed.saturating_mul(self.per_second as u64);
+        self.milli_tokens = (self.milli_tokens as u64)
+            .saturating_add(refill)
+            .min(self.burst as u64 * 1000) as u32;
+
+        if self.milli_tokens >= 1000 {
+            self.milli_tokens -= 1000;
+            true
+        } else {
+            self.dropped = self.dropped.saturating_add(1);
+            false
+        }
+    }
+
+    /// Counts records dropped for another reason, e.g. a full queue.
+    pub fn count_dropped(&mut self, count: u16) {
+        self.dropped = self.dropped.saturating_add(count);
+    }
+
+    /// Returns and resets the number of dropped records.
+    pub fn take_dropped(&mut self) -> u16 {
+        core::mem::take(&mut self.dropped)
+    }
+}
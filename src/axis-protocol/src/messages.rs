@@ -1,9 +1,166 @@
 use serde::{Deserialize, Serialize};
-use defmt::Format;
+
+use crate::auth::{AuthError, Key, Nonce, Proof};
+use crate::clock::{ClockError, ClockReading};
+use crate::decode::DecodeStats;
+use crate::delta::CompactFrame;
+use crate::events::Event;
+use crate::handshake::{DeviceInfo, Hello};
+use crate::history::{HistoryError, SamplePage, ShotId, ShotPage};
+use crate::logs::{LogLevel, LogRecord};
+use crate::params::{ParamError, ParamId, ParamInfo, ParamValue, MAX_PARAMS};
+use crate::telemetry::{TelemetryEncoding, TelemetryFrame, Topic};
+use crate::units::{Bar, Celsius};
+use crate::update::{Chunk, Sha256Digest, UpdateError};
+use heapless::Vec;
 
 /// Events created by the host (MCU) to send back to the client
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, Format)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
 #[repr(u8)]
 pub enum Messages {
-    ThermocoupleReadout { deg_celcius: f32 } = 0,
+    ThermocoupleReadout { temperature: Celsius } = 0,
+    Event(Event) = 1,
+    PressureReadout { pressure: Bar } = 2,
+    RtcTime { hour: u8, minute: u8, second: u8 } = 3,
+    Telemetry(TelemetryFrame) = 4,
+    Log(LogRecord) = 5,
+    /// Sent instead of [Messages::Telemetry] with [TelemetryEncoding::Delta].
+    CompactTelemetry(CompactFrame) = 6,
+}
+
+/// Requests sent by the client to the host (MCU).
+///
+/// Every command is answered with exactly one [Response] carrying the sequence number of the
+/// [MessageHeader](crate::MessageHeader) the command was sent with.
+///
+/// `Hello` must stay the first variant so that every protocol version can decode it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+// Only one command is decoded at a time, so the size of WriteChunk is not worth boxing.
+#[allow(clippy::large_enum_variant)]
+pub enum Commands {
+    Hello(Hello) = 0,
+    Ping = 1,
+    SetSetpoint { setpoint: Celsius } = 2,
+    StartShot = 3,
+    StopShot = 4,
+    ReadConfig = 5,
+    ListParams = 6,
+    DescribeParam { id: ParamId } = 7,
+    GetParam { id: ParamId } = 8,
+    SetParam { id: ParamId, value: ParamValue } = 9,
+    /// Publish `topic` every `interval_ms`, replacing any previous interval.
+    Subscribe { topic: Topic, interval_ms: u32 } = 10,
+    Unsubscribe { topic: Topic } = 11,
+    /// Forward log records of `level` and above, or none at all.
+    SetLogLevel { level: Option<LogLevel> } = 12,
+    /// Starts a firmware update of `image_len` bytes, see [update](crate::update).
+    BeginUpdate { image_len: u32 } = 13,
+    WriteChunk(Chunk) = 14,
+    VerifyUpdate { sha256: Sha256Digest } = 15,
+    /// Boots the verified image. The host (MCU) reboots after answering.
+    CommitUpdate = 16,
+    AbortUpdate = 17,
+    /// How batched [Topic::Samples] are sent.
+    SetTelemetryEncoding { encoding: TelemetryEncoding } = 18,
+    /// Counts of packets the host (MCU) could not decode, answered with [Responses::DecodeStats].
+    ReadDiagnostics = 19,
+    /// Sets the pre-shared key for authenticated sessions, or removes it. Only accepted over
+    /// USB, see [auth](crate::auth).
+    ProvisionKey { key: Option<Key> } = 20,
+    Challenge { client_nonce: Nonce } = 21,
+    Authenticate { proof: Proof } = 22,
+    /// Lists recorded shots with an id above `after`, see [history](crate::history).
+    ListShots { after: Option<ShotId> } = 23,
+    /// Reads the samples of a shot, starting at the `offset`th one.
+    ReadShot { id: ShotId, offset: u32 } = 24,
+    /// Marks a shot as downloaded.
+    AcknowledgeShot { id: ShotId } = 25,
+    DeleteShot { id: ShotId } = 26,
+    /// Reads the wall-clock time, see [clock](crate::clock).
+    ReadClock = 27,
+    /// Sets the wall-clock time, in seconds since the Unix epoch.
+    SetClock {
+        #[cfg_attr(feature = "ts", ts(type = "number"))]
+        unix_time: u64,
+    } = 28,
+    /// Asks for the host's (MCU) uptime, to map its timestamps onto the client's clock. The
+    /// client's own time is echoed back in [Responses::ClockSync].
+    SyncClock {
+        #[cfg_attr(feature = "ts", ts(type = "number"))]
+        client_time_us: u64,
+    } = 29,
+}
+
+/// Reply to a [Commands] request, sent by the host (MCU) to the client
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct Response {
+    /// Sequence number of the request this is a reply to.
+    pub sequence: u8,
+    pub body: Responses,
+}
+
+/// `Hello` must stay the first variant so that every protocol version can decode it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+// Only one response is decoded at a time, so the size of ShotSamples is not worth boxing.
+#[allow(clippy::large_enum_variant)]
+pub enum Responses {
+    Hello(DeviceInfo) = 0,
+    Pong = 1,
+    Ok = 2,
+    Error(CommandError) = 3,
+    Config { setpoint: Celsius } = 4,
+    ParamList(Vec<ParamId, MAX_PARAMS>) = 5,
+    ParamInfo(ParamInfo) = 6,
+    /// Current value of a parameter, also sent after it was written.
+    ParamValue { id: ParamId, value: ParamValue } = 7,
+    DecodeStats(DecodeStats) = 8,
+    Challenge { host_nonce: Nonce } = 9,
+    /// The session is open, the host (MCU) seals every packet from now on.
+    Authenticated { proof: Proof } = 10,
+    ShotList(ShotPage) = 11,
+    ShotSamples(SamplePage) = 12,
+    Clock(ClockReading) = 13,
+    /// Microseconds since the host (MCU) booted, taken when the command arrived.
+    ClockSync {
+        #[cfg_attr(feature = "ts", ts(type = "number"))]
+        client_time_us: u64,
+        #[cfg_attr(feature = "ts", ts(type = "number"))]
+        uptime_us: u64,
+    } = 14,
+}
+
+/// Reasons the host (MCU) can reject a command
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum CommandError {
+    Unsupported = 0,
+    InvalidArgument = 1,
+    Busy = 2,
+    /// The command was sent before the [Hello] exchange completed.
+    HandshakeRequired = 3,
+    /// The [Hello] carried a protocol version the host (MCU) cannot talk to.
+    IncompatibleProtocol = 4,
+    Param(ParamError) = 5,
+    Update(UpdateError) = 6,
+    Auth(AuthError) = 7,
+    History(HistoryError) = 8,
+    Clock(ClockError) = 9,
+}
+
+impl Response {
+    pub fn new(sequence: u8, body: Responses) -> Self {
+        Self { sequence, body }
+    }
 }
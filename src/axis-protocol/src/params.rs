@@ -0,0 +1,284 @@
+//! Device settings that can be listed, read and written by the client while the machine runs.
+//!
+//! Every parameter has a stable [ParamId]; ids are never reused once assigned. [DESCRIPTORS]
+//! holds the definition of every parameter known to this protocol version. Values of parameters
+//! flagged [persist](ParamDescriptor::persist) are kept by the host (MCU) in non-volatile memory,
+//! in the format written by [store], and survive a reset.
+
+use crc::{Crc, CRC_16_IBM_SDLC};
+use serde::{Deserialize, Serialize};
+
+use crate::heartbeat::DEFAULT_LINK_TIMEOUT_MS;
+
+pub type ParamId = u16;
+
+/// Longest parameter name sent over the wire.
+pub const MAX_NAME_LEN: usize = 24;
+
+/// Most parameters a device can list.
+pub const MAX_PARAMS: usize = 32;
+
+/// Largest block [store] writes: the length, the id and value of up to [MAX_PARAMS]
+/// parameters, and the CRC.
+pub const MAX_STORED_LEN: usize = LEN_LEN + 1 + MAX_PARAMS * MAX_STORED_ENTRY_LEN + CRC_LEN;
+
+const LEN_LEN: usize = 2;
+const CRC_LEN: usize = 2;
+/// An id and a value, as varints.
+const MAX_STORED_ENTRY_LEN: usize = 3 + 1 + 5;
+const CRC: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_SDLC);
+
+/// Values of the persisted parameters, as kept in non-volatile memory.
+pub type StoredValues = heapless::Vec<(ParamId, ParamValue), MAX_PARAMS>;
+
+pub const BOILER_SETPOINT: ParamId = 1;
+pub const BOILER_OUTPUT_LIMIT: ParamId = 2;
+pub const BOILER_KP: ParamId = 3;
+pub const BOILER_KI: ParamId = 4;
+pub const BOILER_KD: ParamId = 5;
+pub const BOILER_P_LIMIT: ParamId = 6;
+pub const BOILER_I_LIMIT: ParamId = 7;
+pub const BOILER_D_LIMIT: ParamId = 8;
+pub const PRESSURE_LIMIT: ParamId = 9;
+pub const LINK_TIMEOUT: ParamId = 10;
+
+pub const DESCRIPTORS: &[ParamDescriptor] = &[
+    ParamDescriptor::f32(BOILER_SETPOINT, "boiler.setpoint", Unit::DegCelsius, 0.0, 160.0, 93.0, true),
+    ParamDescriptor::f32(BOILER_OUTPUT_LIMIT, "boiler.output_limit", Unit::Percent, 0.0, 100.0, 100.0, true),
+    ParamDescriptor::f32(BOILER_KP, "boiler.kp", Unit::None, 0.0, 1000.0, 10.0, true),
+    ParamDescriptor::f32(BOILER_KI, "boiler.ki", Unit::None, 0.0, 1000.0, 0.5, true),
+    ParamDescriptor::f32(BOILER_KD, "boiler.kd", Unit::None, 0.0, 1000.0, 0.1, true),
+    ParamDescriptor::f32(BOILER_P_LIMIT, "boiler.p_limit", Unit::Percent, 0.0, 100.0, 100.0, true),
+    ParamDescriptor::f32(BOILER_I_LIMIT, "boiler.i_limit", Unit::Percent, 0.0, 100.0, 100.0, true),
+    ParamDescriptor::f32(BOILER_D_LIMIT, "boiler.d_limit", Unit::Percent, 0.0, 100.0, 100.0, true),
+    ParamDescriptor::f32(PRESSURE_LIMIT, "pump.pressure_limit", Unit::Bar, 0.0, 15.0, 12.0, true),
+    ParamDescriptor::u32(LINK_TIMEOUT, "link.timeout", Unit::Milliseconds, 200, 10_000, DEFAULT_LINK_TIMEOUT_MS, true),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum ParamValue {
+    F32(f32) = 0,
+    U32(u32) = 1,
+    Bool(bool) = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum Unit {
+    None = 0,
+    DegCelsius = 1,
+    Bar = 2,
+    Percent = 3,
+    Seconds = 4,
+    Milliliters = 5,
+    Grams = 6,
+    Milliseconds = 7,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum ParamError {
+    UnknownParameter = 0,
+    /// The value is not of the parameter's type.
+    WrongType = 1,
+    OutOfRange = 2,
+}
+
+/// Definition of a parameter, as compiled into both sides.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParamDescriptor {
+    pub id: ParamId,
+    pub name: &'static str,
+    pub unit: Unit,
+    pub min: ParamValue,
+    pub max: ParamValue,
+    pub default: ParamValue,
+    /// Whether the value survives a reboot.
+    pub persist: bool,
+}
+
+/// A [ParamDescriptor] as sent to the client in reply to
+/// [Commands::DescribeParam](crate::messages::Commands::DescribeParam).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct ParamInfo {
+    pub id: ParamId,
+    #[cfg_attr(feature = "ts", ts(type = "string"))]
+    pub name: heapless::String<MAX_NAME_LEN>,
+    pub unit: Unit,
+    pub min: ParamValue,
+    pub max: ParamValue,
+    pub default: ParamValue,
+    pub persist: bool,
+}
+
+/// Looks up the descriptor of `id`.
+pub fn descriptor(id: ParamId) -> Option<&'static ParamDescriptor> {
+    DESCRIPTORS.iter().find(|d| d.id == id)
+}
+
+impl ParamValue {
+    fn same_type(&self, other: &ParamValue) -> bool {
+        core::mem::discriminant(self) == core::mem::discriminant(other)
+    }
+}
+
+impl ParamDescriptor {
+    #[allow(clippy::too_many_arguments)]
+    pub const fn f32(
+        id: ParamId,
+        name: &'static str,
+        unit: Unit,
+        min: f32,
+        max: f32,
+        default: f32,
+        persist: bool,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            unit,
+            min: ParamValue::F32(min),
+            max: ParamValue::F32(max),
+            default: ParamValue::F32(default),
+            persist,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub const fn u32(
+        id: ParamId,
+        name: &'static str,
+        unit: Unit,
+        min: u32,
+        max: u32,
+        default: u32,
+        persist: bool,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            unit,
+            min: ParamValue::U32(min),
+            max: ParamValue::U32(max),
+            default: ParamValue::U32(default),
+            persist,
+        }
+    }
+
+    /// Checks that `value` has this parameter's type and lies within `min..=max`.
+    pub fn validate(&self, value: ParamValue) -> Result<ParamValue, ParamError> {
+        if !value.same_type(&self.default) {
+            return Err(ParamError::WrongType);
+        }
+
+        let in_range = match (value, self.min, self.max) {
+            (ParamValue::F32(v), ParamValue::F32(min), ParamValue::F32(max)) => v >= min && v <= max,
+            (ParamValue::U32(v), ParamValue::U32(min), ParamValue::U32(max)) => v >= min && v <= max,
+            (ParamValue::Bool(_), _, _) => true,
+            _ => false,
+        };
+
+        if in_range {
+            Ok(value)
+        } else {
+            Err(ParamError::OutOfRange)
+        }
+    }
+
+    pub fn info(&self) -> ParamInfo {
+        let mut name = heapless::String::new();
+        for c in self.name.chars() {
+            if name.push(c).is_err() {
+                break;
+            }
+        }
+
+        ParamInfo {
+            id: self.id,
+            name,
+            unit: self.unit,
+            min: self.min,
+            max: self.max,
+            default: self.default,
+            persist: self.persist,
+        }
+    }
+}
+
+/// Serializes `values` into `buf` as a little endian length, the values and their CRC-16,
+/// returning the bytes to write.
+pub fn store<'a>(values: &StoredValues, buf: &'a mut [u8]) -> postcard::Result<&'a [u8]> {
+    if buf.len() < LEN_LEN + CRC_LEN {
+        return Err(postcard::Error::SerializeBufferFull);
+    }
+    let end = buf.len() - CRC_LEN;
+    let len = postcard::to_slice(values, &mut buf[LEN_LEN..end])?.len();
+    let crc = CRC.checksum(&buf[LEN_LEN..LEN_LEN + len]);
+
+    buf[..LEN_LEN].copy_from_slice(&(len as u16).to_le_bytes());
+    buf[LEN_LEN + len..LEN_LEN + len + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+    Ok(&buf[..LEN_LEN + len + CRC_LEN])
+}
+
+/// Reads back the values written by [store], or `None` if `buf` does not hold any, e.g. because
+/// the memory is blank or a write was cut short. Ids and values are not checked against
+/// [DESCRIPTORS], as they may come from an older firmware.
+pub fn load(buf: &[u8]) -> Option<StoredValues> {
+    let len = u16::from_le_bytes(buf.get(..LEN_LEN)?.try_into().ok()?) as usize;
+    let values = buf.get(LEN_LEN..LEN_LEN + len)?;
+    let crc = buf.get(LEN_LEN + len..LEN_LEN + len + CRC_LEN)?;
+    if CRC.checksum(values).to_le_bytes() != crc {
+        return None;
+    }
+    postcard::from_bytes(values).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> StoredValues {
+        let mut values = StoredValues::new();
+        values.push((BOILER_SETPOINT, ParamValue::F32(94.5))).unwrap();
+        values.push((LINK_TIMEOUT, ParamValue::U32(u32::MAX))).unwrap();
+        values.push((u16::MAX, ParamValue::Bool(true))).unwrap();
+        values
+    }
+
+    #[test]
+    fn stored_values_round_trip() {
+        let mut buf = [0xFF; MAX_STORED_LEN];
+        let stored = store(&values(), &mut buf).unwrap().len();
+        assert_eq!(load(&buf[..stored]), Some(values()));
+        assert_eq!(load(&buf), Some(values()));
+    }
+
+    #[test]
+    fn every_parameter_fits() {
+        let mut values = StoredValues::new();
+        while values.push((u16::MAX, ParamValue::U32(u32::MAX))).is_ok() {}
+        let mut buf = [0; MAX_STORED_LEN];
+        assert_eq!(store(&values, &mut buf).unwrap().len(), MAX_STORED_LEN);
+    }
+
+    #[test]
+    fn blank_or_corrupted_memory_holds_no_values() {
+        assert_eq!(load(&[0xFF; MAX_STORED_LEN]), None);
+        assert_eq!(load(&[0; MAX_STORED_LEN]), None);
+
+        let mut buf = [0; MAX_STORED_LEN];
+        let stored = store(&values(), &mut buf).unwrap().len();
+        buf[stored - CRC_LEN - 1] ^= 1;
+        assert_eq!(load(&buf), None);
+        assert_eq!(load(&buf[..stored - 1]), None);
+    }
+}
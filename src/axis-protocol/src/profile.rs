@@ -0,0 +1,254 @@
+//! Espresso shot profiles, shared by the desktop editor and the firmware running them.
+//!
+//! A [Profile] is an ordered list of [Stage]s. Each stage drives the pump towards a [Target],
+//! reaching it along a [Transition] curve, holds the boiler at a temperature setpoint, and ends
+//! as soon as any of its [ExitCondition]s is met. The shot ends after the last stage.
+//!
+//! Everything has a fixed capacity so profiles can be stored and run on the host (MCU) without
+//! allocating.
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::units::{Bar, Celsius, Grams, Milliliters, MillilitersPerSecond};
+
+/// Most stages in a profile.
+pub const MAX_STAGES: usize = 16;
+/// Most exit conditions of one stage.
+pub const MAX_EXIT_CONDITIONS: usize = 4;
+/// Longest profile name, in bytes.
+pub const MAX_NAME_LEN: usize = 32;
+
+/// Highest pressure a stage may target or exit on.
+pub const MAX_PRESSURE: Bar = Bar(12.0);
+/// Highest flow rate a stage may target.
+pub const MAX_FLOW: MillilitersPerSecond = MillilitersPerSecond(10.0);
+/// Highest brew temperature a stage may set.
+pub const MAX_TEMPERATURE: Celsius = Celsius(105.0);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct Profile {
+    #[cfg_attr(feature = "ts", ts(type = "string"))]
+    pub name: heapless::String<MAX_NAME_LEN>,
+    pub stages: Vec<Stage, MAX_STAGES>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct Stage {
+    pub target: Target,
+    /// How the target is approached from where the previous stage ended.
+    pub transition: Transition,
+    /// Time the transition takes. Ignored for [Transition::Step].
+    pub transition_ms: u32,
+    pub temperature: Celsius,
+    /// The stage ends as soon as any of these is met.
+    pub exit: Vec<ExitCondition, MAX_EXIT_CONDITIONS>,
+}
+
+/// What the pump is controlled by during a stage.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum Target {
+    Pressure(Bar) = 0,
+    Flow(MillilitersPerSecond) = 1,
+    /// Open loop, in percent of full pump power.
+    PumpPower(f32) = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum Transition {
+    /// Jumps to the target right away.
+    Step = 0,
+    /// Moves at a constant rate.
+    Linear = 1,
+    /// Starts and ends slowly, moving fastest halfway through.
+    Ease = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum ExitCondition {
+    /// Time since the stage started.
+    Time { ms: u32 } = 0,
+    PressureAbove(Bar) = 1,
+    PressureBelow(Bar) = 2,
+    /// Water pumped since the stage started.
+    Volume(Milliliters) = 3,
+    /// Weight in the cup since the shot started.
+    Weight(Grams) = 4,
+}
+
+/// Why a profile cannot be run. `stage` is the index of the offending stage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum ProfileError {
+    /// The profile has no stages.
+    Empty = 0,
+    /// The stage has no exit condition, so it would never end.
+    NoExitCondition { stage: u8 } = 1,
+    /// A target, setpoint or threshold is negative, not a number or above its maximum.
+    OutOfRange { stage: u8 } = 2,
+}
+
+/// Measurements an [ExitCondition] is checked against, as seen by the running shot.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ShotState {
+    /// Time since the current stage started.
+    pub stage_ms: u32,
+    pub pressure: Bar,
+    /// Water pumped since the current stage started.
+    pub stage_volume: Milliliters,
+    /// Weight in the cup since the shot started.
+    pub weight: Grams,
+}
+
+impl Profile {
+    /// Checks that every stage can be run and ends.
+    ///
+    /// ```
+    /// use axis_protocol::profile::*;
+    /// use axis_protocol::units::{Bar, Celsius, Grams, MillilitersPerSecond};
+    ///
+    /// let mut profile = Profile { name: "Slayer style".try_into().unwrap(), stages: heapless::Vec::new() };
+    /// assert_eq!(profile.validate(), Err(ProfileError::Empty));
+    ///
+    /// profile.stages.push(Stage {
+    ///     target: Target::Flow(MillilitersPerSecond(2.0)),
+    ///     transition: Transition::Step,
+    ///     transition_ms: 0,
+    ///     temperature: Celsius(93.0),
+    ///     exit: heapless::Vec::from_slice(&[ExitCondition::Time { ms: 8000 }, ExitCondition::PressureAbove(Bar(4.0))]).unwrap(),
+    /// }).unwrap();
+    /// profile.stages.push(Stage {
+    ///     target: Target::Pressure(Bar(9.0)),
+    ///     transition: Transition::Ease,
+    ///     transition_ms: 3000,
+    ///     temperature: Celsius(93.0),
+    ///     exit: heapless::Vec::from_slice(&[ExitCondition::Weight(Grams(36.0))]).unwrap(),
+    /// }).unwrap();
+    /// assert_eq!(profile.validate(), Ok(()));
+    ///
+    /// // Profiles serialize to a few bytes per stage
+    /// let mut buf = [0u8; 128];
+    /// let bytes = postcard::to_slice(&profile, &mut buf).unwrap();
+    /// assert_eq!(postcard::from_bytes::<Profile>(bytes).unwrap(), profile);
+    ///
+    /// profile.stages[1].exit.clear();
+    /// assert_eq!(profile.validate(), Err(ProfileError::NoExitCondition { stage: 1 }));
+    /// ```
+    pub fn validate(&self) -> Result<(), ProfileError> {
+        if self.stages.is_empty() {
+            return Err(ProfileError::Empty);
+        }
+        for (index, stage) in self.stages.iter().enumerate() {
+            // MAX_STAGES fits into a u8
+            let index = index as u8;
+            if stage.exit.is_empty() {
+                return Err(ProfileError::NoExitCondition { stage: index });
+            }
+            if !stage.in_range() {
+                return Err(ProfileError::OutOfRange { stage: index });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Stage {
+    /// Whether the stage is over.
+    pub fn is_done(&self, state: &ShotState) -> bool {
+        self.exit.iter().any(|condition| condition.is_met(state))
+    }
+
+    /// The target to control towards `elapsed_ms` into the stage, starting from `previous`, the
+    /// target the previous stage ended with. Targets of a different kind cannot be blended, so
+    /// the stage steps to its own target.
+    ///
+    /// ```
+    /// use axis_protocol::profile::{Stage, Target, Transition};
+    /// use axis_protocol::units::{Bar, Celsius};
+    ///
+    /// let stage = Stage {
+    ///     target: Target::Pressure(Bar(9.0)),
+    ///     transition: Transition::Linear,
+    ///     transition_ms: 4000,
+    ///     temperature: Celsius(93.0),
+    ///     exit: heapless::Vec::new(),
+    /// };
+    /// assert_eq!(stage.target_at(Some(Target::Pressure(Bar(1.0))), 1000), Target::Pressure(Bar(3.0)));
+    /// assert_eq!(stage.target_at(Some(Target::Pressure(Bar(1.0))), 9000), Target::Pressure(Bar(9.0)));
+    /// assert_eq!(stage.target_at(None, 1000), Target::Pressure(Bar(9.0)));
+    /// ```
+    pub fn target_at(&self, previous: Option<Target>, elapsed_ms: u32) -> Target {
+        if self.transition == Transition::Step || elapsed_ms >= self.transition_ms {
+            return self.target;
+        }
+        let progress = self.transition.apply(elapsed_ms as f32 / self.transition_ms as f32);
+        let blend = |from: f32, to: f32| from + (to - from) * progress;
+
+        match (previous, self.target) {
+            (Some(Target::Pressure(from)), Target::Pressure(to)) => Target::Pressure(Bar(blend(from.0, to.0))),
+            (Some(Target::Flow(from)), Target::Flow(to)) => Target::Flow(MillilitersPerSecond(blend(from.0, to.0))),
+            (Some(Target::PumpPower(from)), Target::PumpPower(to)) => Target::PumpPower(blend(from, to)),
+            _ => self.target,
+        }
+    }
+
+    fn in_range(&self) -> bool {
+        let within = |value: f32, max: f32| (0.0..=max).contains(&value);
+        let target = match self.target {
+            Target::Pressure(pressure) => within(pressure.0, MAX_PRESSURE.0),
+            Target::Flow(flow) => within(flow.0, MAX_FLOW.0),
+            Target::PumpPower(power) => within(power, 100.0),
+        };
+        let exit = self.exit.iter().all(|condition| match *condition {
+            ExitCondition::Time { .. } => true,
+            ExitCondition::PressureAbove(pressure) | ExitCondition::PressureBelow(pressure) => {
+                within(pressure.0, MAX_PRESSURE.0)
+            }
+            ExitCondition::Volume(volume) => within(volume.0, f32::MAX),
+            ExitCondition::Weight(weight) => within(weight.0, f32::MAX),
+        });
+        target && exit && within(self.temperature.0, MAX_TEMPERATURE.0)
+    }
+}
+
+impl Transition {
+    /// Maps the fraction of the transition that has passed, from 0 to 1, to the fraction of the
+    /// way to the target.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Transition::Step => 1.0,
+            Transition::Linear => t,
+            // Smoothstep
+            Transition::Ease => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+impl ExitCondition {
+    pub fn is_met(&self, state: &ShotState) -> bool {
+        match *self {
+            ExitCondition::Time { ms } => state.stage_ms >= ms,
+            ExitCondition::PressureAbove(pressure) => state.pressure >= pressure,
+            ExitCondition::PressureBelow(pressure) => state.pressure <= pressure,
+            ExitCondition::Volume(volume) => state.stage_volume >= volume,
+            ExitCondition::Weight(weight) => state.weight >= weight,
+        }
+    }
+}
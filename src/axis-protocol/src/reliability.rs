@@ -0,0 +1,245 @@
+//! Optional acknowledgement layer for packets that must not be lost, such as commands.
+//!
+//! The receiver of a [reliable](crate::MessageId::is_reliable) packet answers with a
+//! [MessageId::Ack] packet whose body is that packet's sequence number, and uses a
+//! [Deduplicator] to drop retransmissions it already handled. The sender keeps each packet in a
+//! [RetransmitQueue] until it is acknowledged, resending it a bounded number of times. Other
+//! packets, like telemetry, stay fire-and-forget.
+
+use heapless::Vec;
+
+use crate::{MessageHeader, MessageId};
+
+/// How long to wait for an ack before resending.
+pub const DEFAULT_ACK_TIMEOUT_MS: u64 = 50;
+/// How many times a packet is resent before giving up.
+pub const DEFAULT_MAX_RETRANSMITS: u8 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReliabilityError {
+    /// Too many packets are waiting for an ack.
+    QueueFull,
+    /// The packet is larger than a queue entry.
+    PacketTooLarge,
+    /// A packet with this sequence number is already waiting for an ack.
+    DuplicateSequence,
+}
+
+/// Serializes the ack for the packet numbered `acked` into `buf`. The ack itself is numbered
+/// `sequence`, the sender's next one, so that it is sealed and checked for replays like any
+/// other packet in an authenticated session.
+pub fn encode_ack(sequence: u8, acked: u8, buf: &mut [u8]) -> postcard::Result<&mut [u8]> {
+    crate::encode_packet(MessageHeader::new(sequence, MessageId::Ack), &acked, buf)
+}
+
+struct Pending<const SIZE: usize> {
+    sequence: u8,
+    packet: Vec<u8, SIZE>,
+    retransmits: u8,
+    due_ms: u64,
+}
+
+pub enum Retransmit<'a> {
+    /// Write this packet again.
+    Resend(&'a [u8]),
+    /// The packet with this sequence number was never acknowledged and has been dropped.
+    GaveUp { sequence: u8 },
+}
+
+/// Holds up to `N` unacknowledged packets of at most `SIZE` bytes. Times are milliseconds on
+/// any monotonic clock.
+///
+/// ```
+/// use axis_protocol::reliability::{Retransmit, RetransmitQueue};
+///
+/// let mut queue = RetransmitQueue::<4, 32>::new(50, 1);
+/// queue.track(7, &[1, 2, 3], 0).unwrap();
+///
+/// assert!(queue.poll(10).is_none());
+/// assert!(matches!(queue.poll(50), Some(Retransmit::Resend(&[1, 2, 3]))));
+/// assert!(matches!(queue.poll(100), Some(Retransmit::GaveUp { sequence: 7 })));
+/// assert!(!queue.ack(7));
+/// ```
+pub struct RetransmitQueue<const N: usize, const SIZE: usize> {
+    pending: Vec<Pending<SIZE>, N>,
+    timeout_ms: u64,
+    max_retransmits: u8,
+}
+
+impl<const N: usize, const SIZE: usize> RetransmitQueue<N, SIZE> {
+    pub const fn new(timeout_ms: u64, max_retransmits: u8) -> Self {
+        Self {
+            pending: Vec::new(),
+            timeout_ms,
+            max_retransmits,
+        }
+    }
+
+    /// Starts waiting for the ack of `packet`, which was just sent at `now_ms`.
+    pub fn track(&mut self, sequence: u8, packet: &[u8], now_ms: u64) -> Result<(), ReliabilityError> {
+        if self.pending.iter().any(|p| p.sequence == sequence) {
+            return Err(ReliabilityError::DuplicateSequence);
+        }
+
+        let packet = Vec::from_slice(packet).map_err(|_| ReliabilityError::PacketTooLarge)?;
+        self.pending
+            .push(Pending {
+                sequence,
+                packet,
+                retransmits: 0,
+                due_ms: now_ms + self.timeout_ms,
+            })
+            .map_err(|_| ReliabilityError::QueueFull)
+    }
+
+    /// Handles an ack, returning `false` if nothing was waiting for it.
+    pub fn ack(&mut self, sequence: u8) -> bool {
+        match self.pending.iter().position(|p| p.sequence == sequence) {
+            Some(i) => {
+                self.pending.swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// The earliest time [poll](Self::poll) has work to do.
+    pub fn next_due(&self) -> Option<u64> {
+        self.pending.iter().map(|p| p.due_ms).min()
+    }
+
+    /// Returns the next packet whose ack timed out at `now_ms`. Call repeatedly until it returns
+    /// `None`.
+    pub fn poll(&mut self, now_ms: u64) -> Option<Retransmit<'_>> {
+        let i = self.pending.iter().position(|p| p.due_ms <= now_ms)?;
+
+        if self.pending[i].retransmits >= self.max_retransmits {
+            let sequence = self.pending.swap_remove(i).sequence;
+            return Some(Retransmit::GaveUp { sequence });
+        }
+
+        let pending = &mut self.pending[i];
+        pending.retransmits += 1;
+        pending.due_ms = now_ms + self.timeout_ms;
+        Some(Retransmit::Resend(&pending.packet))
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+/// Sequences older than the newest received by this much are dropped as duplicates.
+const DEDUP_WINDOW: u8 = 64;
+
+/// Drops retransmitted packets, so each is only handled once.
+///
+/// Like the replay window of [Opener](crate::auth::Opener), it remembers which of the
+/// [DEDUP_WINDOW] sequence numbers up to the newest one were received, rather than a fixed set
+/// of numbers, so a number is free again once the sequence wrapped around. Every packet received
+/// has to be passed in, not only reliable ones, so the window keeps up with the sender's counter.
+///
+/// ```
+/// use axis_protocol::reliability::Deduplicator;
+///
+/// let mut dedup = Deduplicator::new();
+/// assert!(dedup.accept(3));
+/// assert!(dedup.accept(40));
+/// assert!(!dedup.accept(40));
+/// // Older than the newest, but not received yet
+/// assert!(dedup.accept(39));
+/// ```
+pub struct Deduplicator {
+    newest: Option<u8>,
+    /// Bit `n` is set if `newest - n` was received.
+    seen: u64,
+}
+
+impl Deduplicator {
+    pub const fn new() -> Self {
+        Self { newest: None, seen: 0 }
+    }
+
+    /// Returns `true` the first time `sequence` is seen, and `false` for a duplicate or a
+    /// packet too old to tell.
+    pub fn accept(&mut self, sequence: u8) -> bool {
+        let Some(newest) = self.newest else {
+            self.newest = Some(sequence);
+            self.seen = 1;
+            return true;
+        };
+
+        // Sequence numbers up to half the range ahead are new, the rest are behind
+        let ahead = sequence.wrapping_sub(newest);
+        if ahead != 0 && ahead < 128 {
+            self.seen = if ahead >= DEDUP_WINDOW { 0 } else { self.seen << ahead };
+            self.seen |= 1;
+            self.newest = Some(sequence);
+            return true;
+        }
+
+        let age = newest.wrapping_sub(sequence);
+        if age >= DEDUP_WINDOW || self.seen & (1 << age) != 0 {
+            return false;
+        }
+        self.seen |= 1 << age;
+        true
+    }
+
+    /// Forgets every sequence number, e.g. when the peer reconnects.
+    pub fn reset(&mut self) {
+        self.newest = None;
+        self.seen = 0;
+    }
+}
+
+impl Default for Deduplicator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_reused_sequence_after_wraparound() {
+        let mut dedup = Deduplicator::new();
+        assert!(dedup.accept(3));
+        assert!(dedup.accept(40));
+
+        // Every number once more, as a session numbering all its packets goes through them
+        for sequence in (41..=255).chain(0..40) {
+            assert!(dedup.accept(sequence), "sequence {sequence}");
+        }
+        assert!(dedup.accept(40));
+        assert!(!dedup.accept(40));
+    }
+
+    #[test]
+    fn drops_duplicates_within_the_window() {
+        let mut dedup = Deduplicator::new();
+        for sequence in 250..=255 {
+            assert!(dedup.accept(sequence));
+        }
+        assert!(dedup.accept(2));
+        assert!(!dedup.accept(253));
+        assert!(dedup.accept(1));
+        assert!(!dedup.accept(1));
+    }
+
+    #[test]
+    fn drops_packets_older_than_the_window() {
+        let mut dedup = Deduplicator::new();
+        assert!(dedup.accept(0));
+        assert!(dedup.accept(100));
+        assert!(!dedup.accept(100 - DEDUP_WINDOW));
+        assert!(dedup.accept(101 - DEDUP_WINDOW));
+    }
+}
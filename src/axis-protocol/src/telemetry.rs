@@ -0,0 +1,270 @@
+//! Periodic sensor data streamed by the host (MCU). The client subscribes to each [Topic] at the
+//! interval it wants, and the host (MCU) only publishes subscribed topics.
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::units::{Bar, Celsius, MillilitersPerSecond};
+
+/// Shortest publishing interval a topic can be subscribed at.
+pub const MIN_INTERVAL_MS: u32 = 10;
+
+pub const TOPIC_COUNT: usize = 4;
+
+/// Most samples batched into one [TelemetryFrame].
+pub const MAX_FRAME_SAMPLES: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum Topic {
+    /// Published as [Messages::ThermocoupleReadout](crate::messages::Messages::ThermocoupleReadout)
+    BoilerTemperature = 0,
+    /// Published as [Messages::PressureReadout](crate::messages::Messages::PressureReadout)
+    Pressure = 1,
+    /// Published as [Messages::RtcTime](crate::messages::Messages::RtcTime)
+    RtcTime = 2,
+    /// Sampled at the subscribed interval and published in batches as
+    /// [Messages::Telemetry](crate::messages::Messages::Telemetry)
+    Samples = 3,
+}
+
+impl Topic {
+    pub const ALL: [Topic; TOPIC_COUNT] = [
+        Topic::BoilerTemperature,
+        Topic::Pressure,
+        Topic::RtcTime,
+        Topic::Samples,
+    ];
+}
+
+/// How [Topic::Samples] batches are sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum TelemetryEncoding {
+    /// As [Messages::Telemetry](crate::messages::Messages::Telemetry), with full precision.
+    #[default]
+    Full = 0,
+    /// As [Messages::CompactTelemetry](crate::messages::Messages::CompactTelemetry), see
+    /// [delta](crate::delta).
+    Delta = 1,
+}
+
+/// One reading of every channel. Channels the board cannot measure are `NaN`, which becomes
+/// `null` in JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct Channels {
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub boiler: Celsius,
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub pressure: Bar,
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub flow: MillilitersPerSecond,
+    /// Pump output, 0 to 100 %.
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub pump_duty: f32,
+    /// Heater output, 0 to 100 %.
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub heater_duty: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct TelemetrySample {
+    /// Microseconds after the frame's `base_timestamp_us` the sample was taken at.
+    pub offset_us: u32,
+    pub channels: Channels,
+}
+
+/// A batch of samples sharing one device timestamp, so bursty delivery does not distort the
+/// time series.
+///
+/// ```
+/// use axis_protocol::telemetry::{Channels, TelemetryFrame};
+/// use axis_protocol::units::{Bar, Celsius, MillilitersPerSecond};
+///
+/// let channels = Channels {
+///     boiler: Celsius(93.0),
+///     pressure: Bar(9.0),
+///     flow: MillilitersPerSecond(2.0),
+///     pump_duty: 80.0,
+///     heater_duty: f32::NAN,
+/// };
+///
+/// let mut frame = TelemetryFrame::new();
+/// frame.push(5_000_000, channels).unwrap();
+/// frame.push(5_010_000, channels).unwrap();
+///
+/// let timestamps: Vec<u64> = frame.iter().map(|(t, _)| t).collect();
+/// assert_eq!(timestamps, [5_000_000, 5_010_000]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct TelemetryFrame {
+    /// Microseconds since the host (MCU) booted, at the first sample.
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub base_timestamp_us: u64,
+    pub samples: Vec<TelemetrySample, MAX_FRAME_SAMPLES>,
+}
+
+impl TelemetryFrame {
+    pub const fn new() -> Self {
+        Self {
+            base_timestamp_us: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.samples.is_full()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Adds a sample taken at `timestamp_us`. Gives the channels back if the frame is full or
+    /// the sample is too far from the first one to be stored as an offset.
+    pub fn push(&mut self, timestamp_us: u64, channels: Channels) -> Result<(), Channels> {
+        if self.samples.is_empty() {
+            self.base_timestamp_us = timestamp_us;
+        }
+
+        let offset = timestamp_us
+            .checked_sub(self.base_timestamp_us)
+            .and_then(|offset| u32::try_from(offset).ok());
+        let Some(offset_us) = offset else {
+            return Err(channels);
+        };
+
+        self.samples
+            .push(TelemetrySample { offset_us, channels })
+            .map_err(|sample| sample.channels)
+    }
+
+    /// The samples with their absolute device timestamps. Timestamps of a malformed frame from
+    /// the peer wrap around rather than overflow.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &Channels)> {
+        self.samples
+            .iter()
+            .map(|s| (self.base_timestamp_us.wrapping_add(s.offset_us as u64), &s.channels))
+    }
+}
+
+impl Default for TelemetryFrame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decides which subscribed topics are due for publishing. Times are milliseconds on any
+/// monotonic clock.
+///
+/// ```
+/// use axis_protocol::telemetry::{Scheduler, Topic};
+///
+/// let mut scheduler = Scheduler::new();
+/// scheduler.subscribe(Topic::BoilerTemperature, 100, 0).unwrap();
+/// scheduler.subscribe(Topic::RtcTime, 1000, 0).unwrap();
+///
+/// assert_eq!(scheduler.next_due(), Some(0));
+/// assert_eq!(scheduler.poll(0), Some(Topic::BoilerTemperature));
+/// assert_eq!(scheduler.poll(0), Some(Topic::RtcTime));
+/// assert_eq!(scheduler.poll(0), None);
+/// assert_eq!(scheduler.next_due(), Some(100));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Scheduler {
+    slots: [Option<Slot>; TOPIC_COUNT],
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Slot {
+    interval_ms: u32,
+    due_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SubscribeError {
+    /// The interval is shorter than [MIN_INTERVAL_MS].
+    IntervalTooShort,
+}
+
+impl Scheduler {
+    pub const fn new() -> Self {
+        Self {
+            slots: [None; TOPIC_COUNT],
+        }
+    }
+
+    /// Starts publishing `topic` every `interval_ms`, beginning at `now_ms`. Subscribing to an
+    /// already subscribed topic changes its interval.
+    pub fn subscribe(&mut self, topic: Topic, interval_ms: u32, now_ms: u64) -> Result<(), SubscribeError> {
+        if interval_ms < MIN_INTERVAL_MS {
+            return Err(SubscribeError::IntervalTooShort);
+        }
+
+        self.slots[topic as usize] = Some(Slot {
+            interval_ms,
+            due_ms: now_ms,
+        });
+        Ok(())
+    }
+
+    pub fn unsubscribe(&mut self, topic: Topic) {
+        self.slots[topic as usize] = None;
+    }
+
+    pub fn unsubscribe_all(&mut self) {
+        self.slots = [None; TOPIC_COUNT];
+    }
+
+    pub fn is_subscribed(&self, topic: Topic) -> bool {
+        self.slots[topic as usize].is_some()
+    }
+
+    /// The earliest time a topic is due, or `None` with no subscriptions.
+    pub fn next_due(&self) -> Option<u64> {
+        self.slots.iter().flatten().map(|s| s.due_ms).min()
+    }
+
+    /// Returns a topic due at `now_ms` and schedules its next publication, or `None` once no
+    /// topic is due. Call repeatedly until it returns `None`.
+    ///
+    /// A topic that fell behind by more than one interval is rescheduled from `now_ms`
+    /// instead of publishing the missed samples back to back.
+    pub fn poll(&mut self, now_ms: u64) -> Option<Topic> {
+        for topic in Topic::ALL {
+            let Some(slot) = &mut self.slots[topic as usize] else {
+                continue;
+            };
+
+            if slot.due_ms > now_ms {
+                continue;
+            }
+
+            let next = slot.due_ms + slot.interval_ms as u64;
+            slot.due_ms = if next <= now_ms { now_ms + slot.interval_ms as u64 } else { next };
+            return Some(topic);
+        }
+        None
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,301 @@
+//! Links the protocol runs over, so the code handling packets does not depend on USB or a
+//! network stack. A [Transport] exchanges whole packets, and [FramedStream] provides one over
+//! any byte stream, such as USB CDC or TCP, using [framing](crate::framing). Under the `std`
+//! feature, [loopback] connects two endpoints in memory for testing without hardware.
+
+use core::fmt::Debug;
+
+use embedded_io_async::{Read, Write};
+
+use crate::framing::{self, FeedResult, FrameDecoder, FrameError};
+
+/// TCP port the host (MCU) listens on.
+pub const DEFAULT_TCP_PORT: u16 = 4520;
+
+/// Bytes read from the stream at once, one full speed USB packet.
+const READ_LEN: usize = 64;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransportError<E> {
+    /// The link failed, e.g. USB was unplugged.
+    Link(E),
+    /// The peer closed the link.
+    Closed,
+    /// A frame was corrupted or too large, and was dropped. The link is still usable.
+    Frame(FrameError),
+}
+
+/// A link carrying packets as serialized by [encode_packet](crate::encode_packet).
+#[allow(async_fn_in_trait)]
+pub trait Transport {
+    type Error: Debug;
+
+    /// Sends `packet` as one frame. Dropping the future may leave part of the frame on the
+    /// link, which the next send has to keep from corrupting the frame after it.
+    async fn send(&mut self, packet: &[u8]) -> Result<(), TransportError<Self::Error>>;
+
+    /// Waits for the next packet. Cancel safe, so it can race against sending: if the future is
+    /// dropped, no data is lost.
+    async fn receive(&mut self) -> Result<&[u8], TransportError<Self::Error>>;
+}
+
+/// A [Transport] over a byte stream, carrying frames of up to `N` bytes, see
+/// [max_frame_len](framing::max_frame_len).
+pub struct FramedStream<S, const N: usize> {
+    stream: S,
+    decoder: FrameDecoder<N>,
+    input: [u8; READ_LEN],
+    start: usize,
+    end: usize,
+    /// The frame being sent, or the packet last received.
+    buf: [u8; N],
+    /// Set while a frame is being written, so a send dropped halfway is noticed by the next.
+    interrupted: bool,
+}
+
+impl<S, const N: usize> FramedStream<S, N> {
+    pub const fn new(stream: S) -> Self {
+        Self {
+            stream,
+            decoder: FrameDecoder::new(),
+            input: [0u8; READ_LEN],
+            start: 0,
+            end: 0,
+            buf: [0u8; N],
+            interrupted: false,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// Drops any partially received frame, e.g. after the peer reconnects.
+    pub fn reset(&mut self) {
+        self.decoder.reset();
+        self.start = 0;
+        self.end = 0;
+    }
+}
+
+impl<S: Read + Write, const N: usize> Transport for FramedStream<S, N> {
+    type Error = S::Error;
+
+    async fn send(&mut self, packet: &[u8]) -> Result<(), TransportError<S::Error>> {
+        // End what a dropped send left of its frame, so the peer drops that rather than this one
+        if self.interrupted {
+            self.stream.write_all(&[framing::FRAME_DELIMITER]).await.map_err(TransportError::Link)?;
+            self.interrupted = false;
+        }
+
+        let len = framing::encode_frame(packet, &mut self.buf).map_err(TransportError::Frame)?;
+        self.interrupted = true;
+        self.stream.write_all(&self.buf[..len]).await.map_err(TransportError::Link)?;
+        self.interrupted = false;
+        self.stream.flush().await.map_err(TransportError::Link)
+    }
+
+    async fn receive(&mut self) -> Result<&[u8], TransportError<S::Error>> {
+        loop {
+            while self.start < self.end {
+                let (result, remaining) = match self.decoder.feed(&self.input[self.start..self.end]) {
+                    FeedResult::Consumed => (None, 0),
+                    FeedResult::Frame { packet, remaining } => {
+                        // The decoder holds at most N bytes
+                        self.buf[..packet.len()].copy_from_slice(packet);
+                        (Some(Ok(packet.len())), remaining.len())
+                    }
+                    FeedResult::Error { error, remaining } => (Some(Err(error)), remaining.len()),
+                };
+                self.start = self.end - remaining;
+
+                match result {
+                    Some(Ok(len)) => return Ok(&self.buf[..len]),
+                    Some(Err(error)) => return Err(TransportError::Frame(error)),
+                    None => {}
+                }
+            }
+
+            let len = self.stream.read(&mut self.input).await.map_err(TransportError::Link)?;
+            if len == 0 {
+                return Err(TransportError::Closed);
+            }
+            self.start = 0;
+            self.end = len;
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub use loopback::{loopback, LoopbackStream};
+
+#[cfg(feature = "std")]
+mod loopback {
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+    use std::future::poll_fn;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Poll, Waker};
+
+    use embedded_io_async::{ErrorType, Read, Write};
+
+    use super::FramedStream;
+
+    #[derive(Default)]
+    struct Pipe {
+        bytes: VecDeque<u8>,
+        reader: Option<Waker>,
+        closed: bool,
+    }
+
+    /// One end of an in-memory byte stream, see [loopback].
+    pub struct LoopbackStream {
+        rx: Arc<Mutex<Pipe>>,
+        tx: Arc<Mutex<Pipe>>,
+    }
+
+    /// Two [FramedStream]s connected to each other, for running both ends of the protocol in
+    /// one process. Dropping one end closes the link for the other.
+    ///
+    /// ```
+    /// use std::future::Future;
+    /// use std::pin::pin;
+    /// use std::task::{Context, Poll, Waker};
+    ///
+    /// use axis_protocol::transport::{loopback, Transport};
+    ///
+    /// // Neither end waits while data is buffered, so polling once is enough here
+    /// fn now<F: Future>(future: F) -> F::Output {
+    ///     match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+    ///         Poll::Ready(output) => output,
+    ///         Poll::Pending => panic!("would block"),
+    ///     }
+    /// }
+    ///
+    /// let (mut host, mut client) = loopback::<64>();
+    /// now(host.send(&[1, 0, 2])).unwrap();
+    /// now(host.send(&[3])).unwrap();
+    ///
+    /// assert_eq!(now(client.receive()).unwrap(), &[1, 0, 2]);
+    /// assert_eq!(now(client.receive()).unwrap(), &[3]);
+    /// ```
+    pub fn loopback<const N: usize>() -> (FramedStream<LoopbackStream, N>, FramedStream<LoopbackStream, N>) {
+        let a = Arc::new(Mutex::new(Pipe::default()));
+        let b = Arc::new(Mutex::new(Pipe::default()));
+        (
+            FramedStream::new(LoopbackStream { rx: a.clone(), tx: b.clone() }),
+            FramedStream::new(LoopbackStream { rx: b, tx: a }),
+        )
+    }
+
+    impl ErrorType for LoopbackStream {
+        type Error = Infallible;
+    }
+
+    impl Read for LoopbackStream {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            poll_fn(|cx| {
+                let mut pipe = self.rx.lock().unwrap_or_else(|e| e.into_inner());
+                if pipe.bytes.is_empty() && !pipe.closed && !buf.is_empty() {
+                    pipe.reader = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+
+                let len = buf.len().min(pipe.bytes.len());
+                for (dest, byte) in buf.iter_mut().zip(pipe.bytes.drain(..len)) {
+                    *dest = byte;
+                }
+                Poll::Ready(Ok(len))
+            })
+            .await
+        }
+    }
+
+    impl Write for LoopbackStream {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            let mut pipe = self.tx.lock().unwrap_or_else(|e| e.into_inner());
+            pipe.bytes.extend(buf);
+            if let Some(reader) = pipe.reader.take() {
+                reader.wake();
+            }
+            Ok(buf.len())
+        }
+    }
+
+    impl Drop for LoopbackStream {
+        fn drop(&mut self) {
+            let mut pipe = self.tx.lock().unwrap_or_else(|e| e.into_inner());
+            pipe.closed = true;
+            if let Some(reader) = pipe.reader.take() {
+                reader.wake();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+    use core::future::{poll_fn, Future};
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+
+    use embedded_io_async::ErrorType;
+    use heapless::Vec;
+
+    use super::*;
+
+    /// Takes the first `limit` bytes written, then blocks until `limit` is raised.
+    struct Stalling {
+        written: Vec<u8, 64>,
+        limit: usize,
+    }
+
+    impl ErrorType for Stalling {
+        type Error = Infallible;
+    }
+
+    impl Read for Stalling {
+        async fn read(&mut self, _: &mut [u8]) -> Result<usize, Infallible> {
+            Ok(0)
+        }
+    }
+
+    impl Write for Stalling {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            poll_fn(|_| {
+                let len = buf.len().min(self.limit.saturating_sub(self.written.len()));
+                if len == 0 {
+                    return Poll::Pending;
+                }
+                self.written.extend_from_slice(&buf[..len]).unwrap();
+                Poll::Ready(Ok(len))
+            })
+            .await
+        }
+    }
+
+    fn poll<F: Future>(future: F) -> Poll<F::Output> {
+        pin!(future).poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn dropped_send_does_not_corrupt_the_next_frame() {
+        let mut link = FramedStream::<_, 32>::new(Stalling { written: Vec::new(), limit: 3 });
+        assert!(poll(link.send(&[1, 2, 3, 4, 5, 6])).is_pending());
+
+        link.get_mut().limit = usize::MAX;
+        assert!(matches!(poll(link.send(&[7])), Poll::Ready(Ok(()))));
+
+        let mut decoder = FrameDecoder::<32>::new();
+        let FeedResult::Error { remaining, .. } = decoder.feed(&link.get_mut().written) else {
+            panic!("the cut frame was not ended");
+        };
+        assert!(matches!(decoder.feed(remaining), FeedResult::Frame { packet: [7], remaining: [] }));
+    }
+}
@@ -0,0 +1,122 @@
+//! Physical quantities carried by messages. Each one is a plain `f32` on the wire, so the unit
+//! lives in the type instead of the field name.
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+const PSI_PER_BAR: f32 = 14.503_774;
+#[cfg(feature = "std")]
+const GRAMS_PER_OUNCE: f32 = 28.349_523;
+
+macro_rules! quantity {
+    ($(#[$meta:meta])* $name:ident, $symbol:literal) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        #[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+        #[serde(transparent)]
+        pub struct $name(pub f32);
+
+        impl $name {
+            pub const SYMBOL: &'static str = $symbol;
+
+            pub const fn new(value: f32) -> Self {
+                Self(value)
+            }
+
+            pub const fn get(self) -> f32 {
+                self.0
+            }
+        }
+
+        /// Formats the value followed by its unit, e.g. `93.5 °C`, honouring the precision.
+        #[cfg(feature = "std")]
+        impl core::fmt::Display for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match f.precision() {
+                    Some(precision) => write!(f, "{:.*} {}", precision, self.0, Self::SYMBOL),
+                    None => write!(f, "{} {}", self.0, Self::SYMBOL),
+                }
+            }
+        }
+    };
+}
+
+quantity!(
+    /// Temperature in degrees Celsius.
+    Celsius,
+    "°C"
+);
+quantity!(
+    /// Pressure in bar, relative to the atmosphere.
+    Bar,
+    "bar"
+);
+quantity!(
+    /// Volumetric flow rate.
+    MillilitersPerSecond,
+    "ml/s"
+);
+quantity!(
+    /// Volume of water, e.g. pumped during a shot.
+    Milliliters,
+    "ml"
+);
+quantity!(
+    /// Weight, as measured by a scale.
+    Grams,
+    "g"
+);
+quantity!(
+    /// Electrical power.
+    Watts,
+    "W"
+);
+
+/// Host side conversions for display.
+///
+/// ```
+/// use axis_protocol::units::{Bar, Celsius};
+///
+/// assert_eq!(Celsius(100.0).to_fahrenheit(), 212.0);
+/// assert_eq!(format!("{:.1}", Bar(9.0)), "9.0 bar");
+/// ```
+#[cfg(feature = "std")]
+impl Celsius {
+    pub fn from_fahrenheit(fahrenheit: f32) -> Self {
+        Self((fahrenheit - 32.0) * 5.0 / 9.0)
+    }
+
+    pub fn to_fahrenheit(self) -> f32 {
+        self.0 * 9.0 / 5.0 + 32.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl Bar {
+    pub fn from_psi(psi: f32) -> Self {
+        Self(psi / PSI_PER_BAR)
+    }
+
+    pub fn to_psi(self) -> f32 {
+        self.0 * PSI_PER_BAR
+    }
+
+    pub fn to_kilopascals(self) -> f32 {
+        self.0 * 100.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl Grams {
+    pub fn to_ounces(self) -> f32 {
+        self.0 / GRAMS_PER_OUNCE
+    }
+}
+
+#[cfg(feature = "std")]
+impl Watts {
+    pub fn to_kilowatts(self) -> f32 {
+        self.0 / 1000.0
+    }
+}
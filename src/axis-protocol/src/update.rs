@@ -0,0 +1,191 @@
+//! Firmware updates sent by the client as a sequence of commands, so the same flow works over
+//! any transport the protocol runs on.
+//!
+//! 1. [Commands::BeginUpdate](crate::messages::Commands::BeginUpdate) announces the image size.
+//! 2. [Commands::WriteChunk](crate::messages::Commands::WriteChunk) sends the image in order, one
+//!    [Chunk] at a time. A chunk at the wrong offset is rejected with
+//!    [UpdateError::UnexpectedOffset], telling the client where to resume.
+//! 3. [Commands::VerifyUpdate](crate::messages::Commands::VerifyUpdate) compares the SHA-256
+//!    digest of the whole image.
+//! 4. [Commands::CommitUpdate](crate::messages::Commands::CommitUpdate) marks the image for boot
+//!    and reboots the host (MCU).
+//!
+//! [Commands::AbortUpdate](crate::messages::Commands::AbortUpdate) discards the transfer at any
+//! point before the commit.
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Most image bytes carried by one chunk.
+pub const MAX_CHUNK_LEN: usize = 256;
+
+pub type Sha256Digest = [u8; 32];
+
+/// Part of the image, starting at `offset` bytes into it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+pub struct Chunk {
+    pub offset: u32,
+    pub data: Vec<u8, MAX_CHUNK_LEN>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[repr(u8)]
+pub enum UpdateError {
+    /// No update was begun, or it was aborted.
+    NotStarted = 0,
+    /// An update is already in progress.
+    InProgress = 1,
+    /// The image does not fit into the update partition.
+    TooLarge = 2,
+    /// The chunk does not continue the image. The next chunk must start at `expected`.
+    UnexpectedOffset { expected: u32 } = 3,
+    /// The image was verified or committed before all of it arrived.
+    Incomplete = 4,
+    /// The digest of the received image does not match.
+    DigestMismatch = 5,
+    /// The image must be verified before it can be committed.
+    NotVerified = 6,
+    /// Writing to flash failed.
+    Flash = 7,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Idle,
+    Receiving,
+    Verified,
+}
+
+/// Checks the order and integrity of an incoming image on the host (MCU). Storing the chunks is
+/// left to the caller, after each one is accepted.
+///
+/// ```
+/// use axis_protocol::update::{chunks, sha256, UpdateError, UpdateReceiver};
+///
+/// let image = [0xa5u8; 1000];
+/// let mut receiver = UpdateReceiver::new(64 * 1024);
+///
+/// receiver.begin(image.len() as u32).unwrap();
+/// for chunk in chunks(&image) {
+///     receiver.accept(&chunk).unwrap();
+/// }
+///
+/// assert_eq!(receiver.commit(), Err(UpdateError::NotVerified));
+/// receiver.verify(&sha256(&image)).unwrap();
+/// receiver.commit().unwrap();
+/// ```
+pub struct UpdateReceiver {
+    capacity: u32,
+    state: State,
+    image_len: u32,
+    received: u32,
+    hasher: Sha256,
+}
+
+impl UpdateReceiver {
+    /// Receives images of up to `capacity` bytes, the size of the update partition.
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            state: State::Idle,
+            image_len: 0,
+            received: 0,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Bytes of the image accepted so far.
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.state != State::Idle
+    }
+
+    pub fn begin(&mut self, image_len: u32) -> Result<(), UpdateError> {
+        if self.in_progress() {
+            return Err(UpdateError::InProgress);
+        }
+        if image_len > self.capacity {
+            return Err(UpdateError::TooLarge);
+        }
+
+        self.state = State::Receiving;
+        self.image_len = image_len;
+        self.received = 0;
+        self.hasher = Sha256::new();
+        Ok(())
+    }
+
+    /// Accepts `chunk` if it continues the image. The caller should store it before accepting
+    /// the next one.
+    pub fn accept(&mut self, chunk: &Chunk) -> Result<(), UpdateError> {
+        if self.state != State::Receiving {
+            return Err(UpdateError::NotStarted);
+        }
+        if chunk.offset != self.received {
+            return Err(UpdateError::UnexpectedOffset { expected: self.received });
+        }
+        if self.image_len - self.received < chunk.data.len() as u32 {
+            return Err(UpdateError::TooLarge);
+        }
+
+        self.hasher.update(&chunk.data);
+        self.received += chunk.data.len() as u32;
+        Ok(())
+    }
+
+    /// Compares the digest of the received image with `digest`. A mismatch aborts the update.
+    pub fn verify(&mut self, digest: &Sha256Digest) -> Result<(), UpdateError> {
+        if self.state == State::Idle {
+            return Err(UpdateError::NotStarted);
+        }
+        if self.received != self.image_len {
+            return Err(UpdateError::Incomplete);
+        }
+
+        if self.hasher.clone().finalize().as_slice() != digest {
+            self.abort();
+            return Err(UpdateError::DigestMismatch);
+        }
+        self.state = State::Verified;
+        Ok(())
+    }
+
+    /// Checks that the image may be booted. The caller then marks it for boot and reboots.
+    pub fn commit(&mut self) -> Result<(), UpdateError> {
+        match self.state {
+            State::Idle => Err(UpdateError::NotStarted),
+            State::Receiving if self.received != self.image_len => Err(UpdateError::Incomplete),
+            State::Receiving => Err(UpdateError::NotVerified),
+            State::Verified => {
+                self.state = State::Idle;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn abort(&mut self) {
+        self.state = State::Idle;
+        self.received = 0;
+    }
+}
+
+/// Splits `image` into chunks in the order they must be sent.
+pub fn chunks(image: &[u8]) -> impl Iterator<Item = Chunk> + '_ {
+    image.chunks(MAX_CHUNK_LEN).enumerate().map(|(i, data)| Chunk {
+        offset: (i * MAX_CHUNK_LEN) as u32,
+        // Never longer than MAX_CHUNK_LEN
+        data: Vec::from_slice(data).unwrap_or_default(),
+    })
+}
+
+pub fn sha256(image: &[u8]) -> Sha256Digest {
+    Sha256::digest(image).into()
+}